@@ -0,0 +1,219 @@
+use crate::domain::GachaDataRepository;
+use crate::gacha_api::{SingleGacha, no_such_event};
+use crate::gacha_lint::{LintFinding, lint};
+use crate::gacha_simulation::{MAX_PULLS, expected_pulls, simulate};
+use crate::presentation::{internal_server_error, published_gacha, versioned_json};
+use axum::Json;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{ErrorResponse, IntoResponse, Response, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub struct LintResponse {
+    version: String,
+    findings: Vec<LintFinding>,
+}
+
+/// 公開中の dump の景品とイベントを検査した結果を返す
+///
+/// dump を受け入れたときに警告したものと同じ問題を、同じ順に返す。
+#[tracing::instrument(skip(repository, request_headers))]
+pub async fn get_lint_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    let (version, gacha) = published_gacha(&repository).await?;
+
+    Ok(versioned_json(
+        &version,
+        &request_headers,
+        LintResponse {
+            version: version.0.clone(),
+            findings: lint(&gacha),
+        },
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulateRequest {
+    /// イベント名。省略するか空文字なら通常のガチャ
+    #[serde(default)]
+    event: Option<String>,
+    /// 引く回数 (1 以上 `MAX_PULLS` 以下)
+    pulls: u64,
+    /// 乱数の seed。同じ dump、イベント、回数、seed なら同じ結果になる
+    seed: u64,
+}
+
+#[derive(Serialize)]
+pub struct SimulateResponse {
+    version: String,
+    event_id: Option<i32>,
+    pulls: u64,
+    seed: u64,
+    /// どの景品にも当たらなかった回数
+    misses: u64,
+    prizes: Vec<SimulatedPrizeResponse>,
+}
+
+#[derive(Serialize)]
+pub struct SimulatedPrizeResponse {
+    id: i32,
+    probability: f64,
+    /// 当たった回数
+    count: u64,
+    /// 最初に当たったのが何回目か。当たらなかった場合は null
+    first_hit: Option<u64>,
+    /// 当たるまでに引く回数の期待値。当たらない景品は null
+    expected_pulls: Option<f64>,
+}
+
+/// 公開中の dump の確率で通常のガチャまたはイベントのガチャを引いた結果を返す
+#[tracing::instrument(skip(repository))]
+pub async fn simulate_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    Json(request): Json<SimulateRequest>,
+) -> Result<Response> {
+    if !(1..=MAX_PULLS).contains(&request.pulls) {
+        return Err(ErrorResponse::from(
+            (
+                StatusCode::BAD_REQUEST,
+                format!("pulls must be between 1 and {MAX_PULLS}."),
+            )
+                .into_response(),
+        ));
+    }
+    let (version, gacha) = published_gacha(&repository).await?;
+    let single =
+        SingleGacha::from_query(&gacha, request.event.as_deref()).ok_or_else(no_such_event)?;
+    let event_id = single.event_id;
+
+    let SimulateRequest { pulls, seed, .. } = request;
+    // 最大で数百万回の比較になるため、非同期ランタイムのスレッドを塞がないようにする
+    let (prizes, misses) = tokio::task::spawn_blocking(move || {
+        let prizes = single.prizes(&gacha);
+        let simulation = simulate(&prizes, pulls, seed);
+        let responses = prizes
+            .iter()
+            .zip(expected_pulls(&prizes))
+            .enumerate()
+            .map(|(index, (prize, expected_pulls))| SimulatedPrizeResponse {
+                id: prize.id,
+                probability: prize.probability,
+                count: simulation.counts[index],
+                first_hit: simulation.first_hits[index],
+                expected_pulls,
+            })
+            .collect();
+        (responses, simulation.misses)
+    })
+    .await
+    .map_err(|error| internal_server_error(error.into()))?;
+
+    Ok((
+        [(
+            header::HeaderName::from_static("x-gachadata-version"),
+            version.to_string(),
+        )],
+        Json(SimulateResponse {
+            version: version.0.clone(),
+            event_id,
+            pulls,
+            seed,
+            misses,
+            prizes,
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SimulateRequest, get_lint_handler, simulate_handler};
+    use crate::domain::{ContentHash, GachaData};
+    use crate::presentation::test_support::{SlowRepository, gacha_repository, json_body};
+    use axum::Json;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn lint_findings_of_published_dump_are_listed() {
+        let response = get_lint_handler(State(gacha_repository()), HeaderMap::new())
+            .await
+            .unwrap();
+        let json = json_body(response).await;
+        assert_eq!(json["findings"], serde_json::json!([]));
+
+        let mut snapshot = gacha_repository().gachadata_dump().unwrap();
+        let mut gacha = GachaData::clone(snapshot.gacha.as_deref().unwrap());
+        gacha.prizes[1].event_id = Some(2);
+        snapshot.gacha = Some(Arc::new(gacha));
+        let repository = Arc::new(SlowRepository {
+            snapshot: Some(snapshot),
+            ..SlowRepository::default()
+        });
+        let response = get_lint_handler(State(repository), HeaderMap::new())
+            .await
+            .unwrap();
+        let json = json_body(response).await;
+        assert_eq!(json["version"], ContentHash::of(b"-- current").0);
+        assert_eq!(json["findings"].as_array().unwrap().len(), 1);
+        assert_eq!(json["findings"][0]["rule"], "orphan_event_reference");
+        assert_eq!(json["findings"][0]["event_id"], 2);
+        assert_eq!(json["findings"][0]["prize_ids"], serde_json::json!([2]));
+    }
+
+    #[tokio::test]
+    async fn simulation_is_reproducible_with_the_same_seed() {
+        let repository = gacha_repository();
+        let simulate = |event: Option<&str>, pulls, seed| {
+            let repository = Arc::clone(&repository);
+            let request = SimulateRequest {
+                event: event.map(str::to_owned),
+                pulls,
+                seed,
+            };
+            async move {
+                simulate_handler(State(repository), Json(request))
+                    .await
+                    .into_response()
+            }
+        };
+
+        let json = json_body(simulate(Some("正月"), 10_000, 7).await).await;
+        assert_eq!(
+            json,
+            json_body(simulate(Some("正月"), 10_000, 7).await).await
+        );
+        assert_eq!(json["event_id"], 1);
+        let prizes = json["prizes"].as_array().unwrap();
+        assert_eq!(
+            prizes
+                .iter()
+                .map(|prize| prize["id"].as_i64().unwrap())
+                .collect::<Vec<_>>(),
+            [2, 4]
+        );
+        assert_eq!(prizes[0]["expected_pulls"], 100.0);
+        let total = prizes
+            .iter()
+            .map(|prize| prize["count"].as_u64().unwrap())
+            .sum::<u64>()
+            + json["misses"].as_u64().unwrap();
+        assert_eq!(total, 10_000);
+
+        let regular = json_body(simulate(None, 10, 7).await).await;
+        assert_eq!(regular["event_id"], serde_json::Value::Null);
+        assert_eq!(regular["prizes"].as_array().unwrap().len(), 2);
+
+        assert_eq!(simulate(None, 0, 7).await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            simulate(Some("夏祭り"), 10, 7).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::native_dump::{DumpTarget, connect, quote_identifier};
+use mysql_async::prelude::Queryable;
+use std::collections::BTreeMap;
+
+/// `CHECKSUM TABLE` でテーブルごとのチェックサムを取得する
+///
+/// dump 全体を取得するよりずっと軽く、前回の値と一致すればテーブルの内容は
+/// 変わっていないとみなせるため、dump を取り直すかどうかの判定に使う。
+/// `DUMP_BACKEND=native` 用。`mariadb-dump` では同じ文を `mariadb` コマンドで実行し、
+/// [`parse_checksum_output`] で読む。
+pub async fn checksum_tables(target: &DumpTarget<'_>) -> anyhow::Result<BTreeMap<String, u64>> {
+    let mut conn = connect(target).await?;
+    let rows: Vec<(String, Option<u64>)> =
+        conn.query(checksum_table_statement(target.tables)).await?;
+    conn.disconnect().await?;
+
+    checksums_by_table(
+        target.tables,
+        rows.into_iter().map(|(_, checksum)| checksum).collect(),
+    )
+}
+
+/// `tables` のチェックサムを 1 つの文でまとめて取得する `CHECKSUM TABLE` 文
+pub fn checksum_table_statement(tables: &[String]) -> String {
+    let tables: Vec<_> = tables.iter().map(|table| quote_identifier(table)).collect();
+    format!("CHECKSUM TABLE {}", tables.join(", "))
+}
+
+/// `mariadb --batch --skip-column-names` で実行した `CHECKSUM TABLE` の出力を読む
+///
+/// 各行はタブ区切りの `<database>.<table>` とチェックサムで、NULL は `NULL` と書かれる。
+pub fn parse_checksum_output(
+    tables: &[String],
+    stdout: &[u8],
+) -> anyhow::Result<BTreeMap<String, u64>> {
+    let checksums = String::from_utf8_lossy(stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (_, checksum) = line
+                .rsplit_once('\t')
+                .ok_or_else(|| anyhow::anyhow!("unexpected CHECKSUM TABLE output: {line:?}"))?;
+            match checksum {
+                "NULL" => Ok(None),
+                checksum => Ok(Some(checksum.parse()?)),
+            }
+        })
+        .collect::<anyhow::Result<_>>()?;
+    checksums_by_table(tables, checksums)
+}
+
+fn checksums_by_table(
+    tables: &[String],
+    checksums: Vec<Option<u64>>,
+) -> anyhow::Result<BTreeMap<String, u64>> {
+    anyhow::ensure!(
+        checksums.len() == tables.len(),
+        "CHECKSUM TABLE returned {} rows for {} tables",
+        checksums.len(),
+        tables.len()
+    );
+    // 結果の行はテーブルを指定した順に並ぶ
+    tables
+        .iter()
+        .zip(checksums)
+        .map(|(table, checksum)| {
+            // 存在しないテーブルのチェックサムは NULL になる
+            let checksum =
+                checksum.ok_or_else(|| anyhow::anyhow!("table `{table}` does not exist"))?;
+            Ok((table.clone(), checksum))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum_table_statement, parse_checksum_output};
+    use std::collections::BTreeMap;
+
+    fn tables() -> [String; 2] {
+        ["gachadata".to_owned(), "gacha_events".to_owned()]
+    }
+
+    #[test]
+    fn all_tables_are_checksummed_in_one_statement() {
+        assert_eq!(
+            checksum_table_statement(&tables()),
+            "CHECKSUM TABLE `gachadata`, `gacha_events`"
+        );
+    }
+
+    #[test]
+    fn mariadb_batch_output_is_read_in_table_order() {
+        let output = b"seichiassist.gachadata\t3924571290\nseichiassist.gacha_events\t0\n";
+        assert_eq!(
+            parse_checksum_output(&tables(), output).unwrap(),
+            BTreeMap::from([
+                ("gachadata".to_owned(), 3924571290),
+                ("gacha_events".to_owned(), 0),
+            ])
+        );
+
+        let missing = b"seichiassist.gachadata\t3924571290\nseichiassist.gacha_events\tNULL\n";
+        assert!(parse_checksum_output(&tables(), missing).is_err());
+        assert!(parse_checksum_output(&tables(), b"seichiassist.gachadata\t1\n").is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use crate::domain::DumpVersion;
+use crate::dump_diff::{Row, TableDiff, diff_tables, to_row};
+use crate::sql_dump::{SqlDumpParseError, SqlValue, Table, parse_dump};
+use std::collections::{BTreeMap, HashMap};
+
+const GACHADATA: &str = "gachadata";
+const GACHA_EVENTS: &str = "gacha_events";
+
+/// 2 つの dump の行単位の差分から、「追加: …」のような変更点の説明を作る
+///
+/// `gachadata` と `gacha_events` は SeichiAssist の列 (`probability`・`event_id`・
+/// `event_name` など) を読んで説明し、それ以外のテーブルは件数だけを書く。
+pub fn changelog_between(before: &[u8], after: &[u8]) -> Result<Vec<String>, SqlDumpParseError> {
+    let before = parse_dump(before)?;
+    let after = parse_dump(after)?;
+    let diff = diff_tables(&before, &after);
+    let events = Events::new(&before, &after);
+
+    let mut changes = Vec::new();
+    for (table, table_diff) in &diff.tables {
+        match table.as_str() {
+            GACHA_EVENTS => event_changes(table_diff, &events, &mut changes),
+            GACHADATA => prize_changes(table_diff, &events, &mut changes),
+            _ if !table_diff.is_empty() => changes.push(format!(
+                "変更: {table} ({} 件追加、{} 件削除、{} 件変更)",
+                table_diff.added.len(),
+                table_diff.removed.len(),
+                table_diff.changed.len()
+            )),
+            _ => {}
+        }
+    }
+    Ok(changes)
+}
+
+/// 公開した順 (新しい順) に並んだバージョンの changelog を Markdown にする
+///
+/// 公開日時は `time_zone` (イベントの日時と同じ `GACHA_EVENT_UTC_OFFSET_HOURS`) で表示する。
+pub fn to_markdown(versions: &[DumpVersion], time_zone: &jiff::tz::TimeZone) -> String {
+    let mut markdown = String::from("# ガチャデータ更新履歴\n");
+    for version in versions {
+        markdown.push_str(&format!(
+            "\n## {} (`{}`)\n\n",
+            format_published_at(version.published_at, time_zone),
+            short_version(&version.content_hash.0)
+        ));
+        match &version.changelog {
+            None => markdown.push_str("- 直前のバージョンが履歴にないため、変更点は不明です\n"),
+            Some(changelog) if changelog.changes.is_empty() => {
+                markdown.push_str("- データの変更はありません\n")
+            }
+            Some(changelog) => {
+                for change in &changelog.changes {
+                    markdown.push_str(&format!("- {change}\n"));
+                }
+            }
+        }
+    }
+    markdown
+}
+
+fn short_version(content_hash: &str) -> &str {
+    content_hash.get(..12).unwrap_or(content_hash)
+}
+
+fn format_published_at(
+    published_at: std::time::SystemTime,
+    time_zone: &jiff::tz::TimeZone,
+) -> String {
+    let Ok(timestamp) = jiff::Timestamp::try_from(published_at) else {
+        return "日時不明".to_owned();
+    };
+    timestamp
+        .to_zoned(time_zone.clone())
+        .strftime("%Y-%m-%d %H:%M (UTC%:z)")
+        .to_string()
+}
+
+/// 新旧の dump の `gacha_events` (新しい方を優先して引く)
+struct Events {
+    before: HashMap<SqlValue, Row>,
+    after: HashMap<SqlValue, Row>,
+}
+
+impl Events {
+    fn new(before: &BTreeMap<String, Table>, after: &BTreeMap<String, Table>) -> Self {
+        let by_id = |tables: &BTreeMap<String, Table>| -> HashMap<SqlValue, Row> {
+            let Some(table) = tables.get(GACHA_EVENTS) else {
+                return HashMap::new();
+            };
+            table
+                .rows
+                .iter()
+                .map(|values| to_row(table, values))
+                .map(|row| (column(&row, "id").clone(), row))
+                .collect()
+        };
+        Events {
+            before: by_id(before),
+            after: by_id(after),
+        }
+    }
+
+    fn get(&self, id: &SqlValue) -> Option<&Row> {
+        self.after.get(id).or_else(|| self.before.get(id))
+    }
+
+    /// `gachadata.event_id` の表示。イベントが見つからなければ id のまま
+    fn describe(&self, id: &SqlValue) -> String {
+        match (id, self.get(id)) {
+            (SqlValue::Null, _) => "なし".to_owned(),
+            (_, Some(event)) => format!("「{}」", text(column(event, "event_name"))),
+            (id, None) => format!("#{}", text(id)),
+        }
+    }
+}
+
+fn column<'a>(row: &'a Row, column: &str) -> &'a SqlValue {
+    const NULL: &SqlValue = &SqlValue::Null;
+    row.get(column).unwrap_or(NULL)
+}
+
+/// 値をクォートせずに表示する
+fn text(value: &SqlValue) -> String {
+    match value {
+        SqlValue::Null => "なし".to_owned(),
+        SqlValue::Number(number) => number.clone(),
+        SqlValue::String(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn prize_label(row: &Row) -> String {
+    format!("景品 #{}", text(column(row, "id")))
+}
+
+fn describe_prize(row: &Row, events: &Events) -> String {
+    let probability = text(column(row, "probability"));
+    match column(row, "event_id") {
+        SqlValue::Null => format!("{} (確率 {probability})", prize_label(row)),
+        event_id => format!(
+            "{} (確率 {probability}、イベント{})",
+            prize_label(row),
+            events.describe(event_id)
+        ),
+    }
+}
+
+fn prize_changes(diff: &TableDiff, events: &Events, changes: &mut Vec<String>) {
+    for row in &diff.added {
+        changes.push(format!("追加: {}", describe_prize(row, events)));
+    }
+    for row in &diff.removed {
+        changes.push(format!("削除: {}", describe_prize(row, events)));
+    }
+    for change in &diff.changed {
+        let prize = prize_label(&change.key);
+        for (name, column_change) in &change.columns {
+            changes.push(match name.as_str() {
+                "probability" => format!(
+                    "確率変更: {prize} {} → {}",
+                    text(&column_change.before),
+                    text(&column_change.after)
+                ),
+                "itemstack" => format!("アイテム変更: {prize}"),
+                "event_id" => format!(
+                    "イベント変更: {prize} {} → {}",
+                    events.describe(&column_change.before),
+                    events.describe(&column_change.after)
+                ),
+                other => format!("変更: {prize} の {other}"),
+            });
+        }
+    }
+}
+
+fn period(event: &Row) -> String {
+    format!(
+        "{} 〜 {}",
+        text(column(event, "event_start_time")),
+        text(column(event, "event_end_time"))
+    )
+}
+
+fn event_changes(diff: &TableDiff, events: &Events, changes: &mut Vec<String>) {
+    for event in &diff.added {
+        changes.push(format!(
+            "イベント追加: {} ({})",
+            text(column(event, "event_name")),
+            period(event)
+        ));
+    }
+    // 行の削除はイベントの終了ではない (終了したイベントも行は残る) ため、削除として書く
+    for event in &diff.removed {
+        changes.push(format!(
+            "イベント削除: {}",
+            text(column(event, "event_name"))
+        ));
+    }
+    for change in &diff.changed {
+        let id = column(&change.key, "id");
+        let (Some(before), Some(after)) = (events.before.get(id), events.after.get(id)) else {
+            continue;
+        };
+        let name = text(column(after, "event_name"));
+        let mut period_changed = false;
+        for column_name in change.columns.keys() {
+            match column_name.as_str() {
+                "event_name" => changes.push(format!(
+                    "イベント名変更: {} → {name}",
+                    text(column(before, "event_name"))
+                )),
+                "event_end_time" if !change.columns.contains_key("event_start_time") => {
+                    changes.push(format!(
+                        "イベント終了日時変更: {name} {} → {}",
+                        text(column(before, "event_end_time")),
+                        text(column(after, "event_end_time"))
+                    ));
+                }
+                "event_start_time" | "event_end_time" if !period_changed => {
+                    period_changed = true;
+                    changes.push(format!(
+                        "イベント期間変更: {name} {} → {}",
+                        period(before),
+                        period(after)
+                    ));
+                }
+                "event_start_time" | "event_end_time" => {}
+                other => changes.push(format!("変更: イベント「{name}」の {other}")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changelog_between, to_markdown};
+    use crate::domain::{Changelog, ContentHash, DumpVersion, GachadataDump};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn dump(gachadata_rows: &str, gacha_events_rows: &str) -> String {
+        format!(
+            "\
+CREATE TABLE `gacha_events` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `event_name` varchar(30) NOT NULL,
+  `event_start_time` datetime NOT NULL,
+  `event_end_time` datetime NOT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `gacha_events` VALUES {gacha_events_rows};
+CREATE TABLE `gachadata` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `probability` double NOT NULL,
+  `itemstack` blob DEFAULT NULL,
+  `event_id` int(11) DEFAULT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `gachadata` VALUES {gachadata_rows};
+-- Dump completed
+"
+        )
+    }
+
+    #[test]
+    fn changes_are_described_in_japanese() {
+        let before = dump(
+            "(1,0.01,'sword',NULL),(2,0.1,'apple',1),(3,0.5,'stone',NULL)",
+            "(1,'正月','2024-01-01 00:00:00','2024-01-07 23:59:59'),\
+             (2,'夏祭り','2024-08-01 00:00:00','2024-08-07 23:59:59')",
+        );
+        let after = dump(
+            "(1,0.02,'sword',NULL),(3,0.5,'diamond',2),(4,0.05,'bow',3)",
+            "(2,'夏祭り','2024-08-01 00:00:00','2024-08-14 23:59:59'),\
+             (3,'ハロウィン','2024-10-25 00:00:00','2024-10-31 23:59:59')",
+        );
+
+        assert_eq!(
+            changelog_between(before.as_bytes(), after.as_bytes()).unwrap(),
+            [
+                "イベント追加: ハロウィン (2024-10-25 00:00:00 〜 2024-10-31 23:59:59)",
+                "イベント削除: 正月",
+                "イベント終了日時変更: 夏祭り 2024-08-07 23:59:59 → 2024-08-14 23:59:59",
+                "追加: 景品 #4 (確率 0.05、イベント「ハロウィン」)",
+                "削除: 景品 #2 (確率 0.1、イベント「正月」)",
+                "確率変更: 景品 #1 0.01 → 0.02",
+                "イベント変更: 景品 #3 なし → 「夏祭り」",
+                "アイテム変更: 景品 #3",
+            ]
+        );
+
+        let moved = dump(
+            "(1,0.02,'sword',NULL)",
+            "(2,'夏祭り','2024-08-02 00:00:00','2024-08-08 23:59:59')",
+        );
+        assert_eq!(
+            changelog_between(before.as_bytes(), moved.as_bytes()).unwrap()[1],
+            "イベント期間変更: 夏祭り 2024-08-01 00:00:00 〜 2024-08-07 23:59:59 → 2024-08-02 00:00:00 〜 2024-08-08 23:59:59"
+        );
+    }
+
+    #[test]
+    fn versions_are_rendered_as_markdown_in_given_time_zone() {
+        let version = |dump: &'static [u8], changelog: Option<Changelog>| DumpVersion {
+            content_hash: ContentHash::of(dump),
+            // 2024-01-01 00:00 UTC
+            published_at: UNIX_EPOCH + Duration::from_secs(1_704_067_200),
+            dump: GachadataDump(dump.into()),
+            row_counts: Default::default(),
+            changelog,
+        };
+        let first = version(b"-- first", None);
+        let second = version(
+            b"-- second",
+            Some(Changelog {
+                previous_version: first.content_hash.clone(),
+                changes: vec!["確率変更: 景品 #1 0.01 → 0.02".to_owned()],
+            }),
+        );
+
+        let jst = jiff::tz::Offset::constant(9).to_time_zone();
+        let markdown = to_markdown(&[second.clone(), first.clone()], &jst);
+        assert!(markdown.starts_with(&format!(
+            "# ガチャデータ更新履歴\n\n## 2024-01-01 09:00 (UTC+09:00) (`{}`)\n\n- 確率変更: 景品 #1 0.01 → 0.02\n",
+            &second.content_hash.0[..12]
+        )));
+        assert!(markdown.ends_with("- 直前のバージョンが履歴にないため、変更点は不明です\n"));
+
+        let utc = jiff::tz::TimeZone::UTC;
+        assert!(to_markdown(&[first], &utc).contains("## 2024-01-01 00:00 (UTC+00:00)"));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::sql_dump::{SqlDumpParseError, SqlValue, Table, parse_dump};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// 列名から値への対応。JSON では列名をキーにしたオブジェクトになる
+pub type Row = BTreeMap<String, SqlValue>;
+
+/// 2 つの dump のテーブルごとの差分
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DumpDiff {
+    pub tables: BTreeMap<String, TableDiff>,
+}
+
+impl DumpDiff {
+    pub fn is_empty(&self) -> bool {
+        self.tables.values().all(TableDiff::is_empty)
+    }
+}
+
+/// 主キーで行を突き合わせた差分
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TableDiff {
+    /// 新しい dump にだけある行 (新しい dump での順)
+    pub added: Vec<Row>,
+    /// 古い dump にだけある行 (古い dump での順)
+    pub removed: Vec<Row>,
+    /// 主キーが同じで値が変わった行 (新しい dump での順)
+    pub changed: Vec<RowChange>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RowChange {
+    /// 主キーの列と値
+    pub key: Row,
+    /// 変わった列ごとの変更前後の値
+    pub columns: BTreeMap<String, ColumnChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnChange {
+    pub before: SqlValue,
+    pub after: SqlValue,
+}
+
+/// 2 つの dump を読み、どちらかに含まれるすべてのテーブルの差分を主キーで突き合わせて返す
+///
+/// 片方の dump にしかないテーブルは、そのすべての行を追加または削除として返す。
+/// 主キーのないテーブルは全列をキーとして扱うため、値の変更は削除と追加として現れる。
+/// 片方の dump にしかない列は、もう片方では `NULL` として比較する。
+pub fn diff_dumps(before: &[u8], after: &[u8]) -> Result<DumpDiff, SqlDumpParseError> {
+    Ok(diff_tables(&parse_dump(before)?, &parse_dump(after)?))
+}
+
+/// [`parse_dump`] 済みのテーブル同士の差分を返す
+pub fn diff_tables(before: &BTreeMap<String, Table>, after: &BTreeMap<String, Table>) -> DumpDiff {
+    let empty = Table::default();
+    let tables = before
+        .keys()
+        .chain(after.keys())
+        .map(|table| {
+            let table_before = before.get(table).unwrap_or(&empty);
+            let table_after = after.get(table).unwrap_or(&empty);
+            (table.clone(), diff_table(table_before, table_after))
+        })
+        .collect();
+    DumpDiff { tables }
+}
+
+fn diff_table(before: &Table, after: &Table) -> TableDiff {
+    let mut key_columns = if after.columns.is_empty() {
+        before.primary_key.clone()
+    } else {
+        after.primary_key.clone()
+    };
+    if key_columns.is_empty() {
+        key_columns = if after.columns.is_empty() {
+            before.columns.clone()
+        } else {
+            after.columns.clone()
+        };
+    }
+    let columns: Vec<String> =
+        after
+            .columns
+            .iter()
+            .chain(&before.columns)
+            .fold(Vec::new(), |mut columns, column| {
+                if !columns.contains(column) {
+                    columns.push(column.clone());
+                }
+                columns
+            });
+
+    let before_rows: Vec<Row> = before.rows.iter().map(|row| to_row(before, row)).collect();
+    let after_rows: Vec<Row> = after.rows.iter().map(|row| to_row(after, row)).collect();
+    let key_of = |row: &Row| -> Vec<SqlValue> {
+        key_columns
+            .iter()
+            .map(|column| row.get(column).cloned().unwrap_or(SqlValue::Null))
+            .collect()
+    };
+    let before_by_key: HashMap<Vec<SqlValue>, &Row> =
+        before_rows.iter().map(|row| (key_of(row), row)).collect();
+    let after_by_key: HashMap<Vec<SqlValue>, &Row> =
+        after_rows.iter().map(|row| (key_of(row), row)).collect();
+
+    let mut diff = TableDiff::default();
+    for row in &after_rows {
+        let Some(previous) = before_by_key.get(&key_of(row)) else {
+            diff.added.push(row.clone());
+            continue;
+        };
+        let changed: BTreeMap<String, ColumnChange> = columns
+            .iter()
+            .filter_map(|column| {
+                let before = previous.get(column).cloned().unwrap_or(SqlValue::Null);
+                let after = row.get(column).cloned().unwrap_or(SqlValue::Null);
+                (before != after).then(|| (column.clone(), ColumnChange { before, after }))
+            })
+            .collect();
+        if !changed.is_empty() {
+            diff.changed.push(RowChange {
+                key: key_columns
+                    .iter()
+                    .filter_map(|column| Some((column.clone(), row.get(column)?.clone())))
+                    .collect(),
+                columns: changed,
+            });
+        }
+    }
+    diff.removed = before_rows
+        .iter()
+        .filter(|row| !after_by_key.contains_key(&key_of(row)))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// `table` の 1 行を列名から値への対応にする
+pub fn to_row(table: &Table, values: &[SqlValue]) -> Row {
+    table
+        .columns
+        .iter()
+        .cloned()
+        .zip(values.iter().cloned())
+        .collect()
+}
+
+/// テキスト表示で 1 つの値に使う最大文字数。itemstack の BLOB などは長すぎるので省略する
+const MAX_RENDERED_VALUE_CHARS: usize = 40;
+
+/// 人が読むためのテキスト表示
+///
+/// ```text
+/// gachadata: 1 added, 1 removed, 1 changed
+///   + id=3 probability=0.5 event_id=NULL
+///   - id=2 probability=0.1 event_id=NULL
+///   ~ id=1 probability: 0.01 -> 0.02
+/// ```
+impl std::fmt::Display for DumpDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no differences");
+        }
+        for (table, diff) in self.tables.iter().filter(|(_, diff)| !diff.is_empty()) {
+            writeln!(
+                f,
+                "{table}: {} added, {} removed, {} changed",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.changed.len()
+            )?;
+            for row in &diff.added {
+                writeln!(f, "  + {}", render_row(row))?;
+            }
+            for row in &diff.removed {
+                writeln!(f, "  - {}", render_row(row))?;
+            }
+            for change in &diff.changed {
+                let columns: Vec<String> = change
+                    .columns
+                    .iter()
+                    .map(|(column, change)| {
+                        format!(
+                            "{column}: {} -> {}",
+                            render_value(&change.before),
+                            render_value(&change.after)
+                        )
+                    })
+                    .collect();
+                writeln!(f, "  ~ {} {}", render_row(&change.key), columns.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn render_row(row: &Row) -> String {
+    let columns: Vec<String> = row
+        .iter()
+        .map(|(column, value)| format!("{column}={}", render_value(value)))
+        .collect();
+    columns.join(" ")
+}
+
+fn render_value(value: &SqlValue) -> String {
+    let rendered = value.to_string();
+    if rendered.chars().count() <= MAX_RENDERED_VALUE_CHARS {
+        return rendered;
+    }
+    let truncated: String = rendered.chars().take(MAX_RENDERED_VALUE_CHARS).collect();
+    format!("{truncated}…")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_dumps;
+    use crate::sql_dump::SqlValue;
+
+    fn dump(gachadata_rows: &str, gacha_events_rows: &str) -> String {
+        format!(
+            "\
+CREATE TABLE `gachadata` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `probability` double NOT NULL,
+  `itemstack` blob DEFAULT NULL,
+  `event_id` int(11) DEFAULT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `gachadata` VALUES {gachadata_rows};
+CREATE TABLE `gacha_events` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `event_name` varchar(30) NOT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `gacha_events` VALUES {gacha_events_rows};
+-- Dump completed
+"
+        )
+    }
+
+    #[test]
+    fn rows_are_matched_by_primary_key() {
+        let before = dump(
+            "(1,0.01,'sword',NULL),(2,0.1,'apple',NULL),(3,0.5,'stone',1)",
+            "(1,'new-year')",
+        );
+        let after = dump(
+            "(1,0.02,'sword',NULL),(3,0.5,'stone',1),(4,0.05,'bow',1)",
+            "(1,'new-year')",
+        );
+
+        let diff = diff_dumps(before.as_bytes(), after.as_bytes()).unwrap();
+
+        assert!(diff.tables["gacha_events"].is_empty());
+        let gachadata = &diff.tables["gachadata"];
+        assert_eq!(gachadata.added.len(), 1);
+        assert_eq!(gachadata.added[0]["id"], SqlValue::Number("4".to_owned()));
+        assert_eq!(gachadata.removed.len(), 1);
+        assert_eq!(gachadata.removed[0]["id"], SqlValue::Number("2".to_owned()));
+        assert_eq!(
+            serde_json::to_value(&gachadata.changed).unwrap(),
+            serde_json::json!([{
+                "key": { "id": 1 },
+                "columns": { "probability": { "before": 0.01, "after": 0.02 } }
+            }])
+        );
+
+        assert_eq!(
+            diff.to_string(),
+            "\
+gachadata: 1 added, 1 removed, 1 changed
+  + event_id=1 id=4 itemstack='bow' probability=0.05
+  - event_id=NULL id=2 itemstack='apple' probability=0.1
+  ~ id=1 probability: 0.01 -> 0.02
+"
+        );
+    }
+
+    #[test]
+    fn identical_dumps_have_no_differences() {
+        let dump = dump("(1,0.01,'sword',NULL)", "(1,'new-year')");
+        let diff = diff_dumps(dump.as_bytes(), dump.as_bytes()).unwrap();
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no differences\n");
+    }
+
+    #[test]
+    fn long_values_are_truncated_in_text() {
+        let long = "x".repeat(100);
+        let before = dump("(1,0.01,'sword',NULL)", "(1,'new-year')");
+        let after = dump(&format!("(1,0.01,'{long}',NULL)"), "(1,'new-year')");
+
+        let text = diff_dumps(before.as_bytes(), after.as_bytes())
+            .unwrap()
+            .to_string();
+        assert!(text.contains(&format!("itemstack: 'sword' -> '{}…", &long[..39])));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::domain::{ContentHash, DumpVersion};
+use std::time::{Duration, SystemTime};
+
+/// 履歴に残す dump の数と期間
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    pub max_versions: usize,
+    pub max_age: Duration,
+}
+
+/// 公開した dump の履歴 (新しい順)
+///
+/// 最新のバージョン (公開中の dump) は保持期間を過ぎても削除しない。
+#[derive(Debug, Clone, Default)]
+pub struct DumpHistory {
+    versions: Vec<DumpVersion>,
+}
+
+impl DumpHistory {
+    pub fn from_versions(mut versions: Vec<DumpVersion>) -> Self {
+        versions.sort_by_key(|version| std::cmp::Reverse(version.published_at));
+        DumpHistory { versions }
+    }
+
+    pub fn versions(&self) -> &[DumpVersion] {
+        &self.versions
+    }
+
+    /// バージョンが `id` の dump を返す (同じ内容が複数回公開されていれば最も新しいもの)
+    pub fn get(&self, id: &ContentHash) -> Option<&DumpVersion> {
+        self.versions
+            .iter()
+            .find(|version| &version.content_hash == id)
+    }
+
+    /// 公開した dump を履歴に追加し、保持期間を過ぎたものを削除する
+    ///
+    /// 直前に公開したものと同じバージョンであれば追加せず `false` を返す。
+    pub fn record(&mut self, version: DumpVersion, retention: Retention, now: SystemTime) -> bool {
+        let is_new = self
+            .versions
+            .first()
+            .is_none_or(|latest| latest.content_hash != version.content_hash);
+        if is_new {
+            self.versions.insert(0, version);
+        }
+        self.prune(retention, now);
+        is_new
+    }
+
+    fn prune(&mut self, retention: Retention, now: SystemTime) {
+        self.versions.truncate(retention.max_versions.max(1));
+        let mut index = 0;
+        self.versions.retain(|version| {
+            let keep = index == 0
+                || now
+                    .duration_since(version.published_at)
+                    .is_ok_and(|age| age <= retention.max_age)
+                    // 時計が巻き戻った場合も残す
+                    || now < version.published_at;
+            index += 1;
+            keep
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DumpHistory, Retention};
+    use crate::domain::{ContentHash, DumpVersion, GachadataDump};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn version(dump: &'static [u8], published_day: u32) -> DumpVersion {
+        DumpVersion {
+            content_hash: ContentHash::of(dump),
+            published_at: UNIX_EPOCH + DAY * published_day,
+            dump: GachadataDump(dump.into()),
+            row_counts: Default::default(),
+            changelog: None,
+        }
+    }
+
+    fn day(day: u32) -> SystemTime {
+        UNIX_EPOCH + DAY * day
+    }
+
+    const RETENTION: Retention = Retention {
+        max_versions: 3,
+        max_age: Duration::from_secs(30 * 24 * 60 * 60),
+    };
+
+    #[test]
+    fn same_version_is_not_recorded_twice_in_a_row() {
+        let mut history = DumpHistory::default();
+        assert!(history.record(version(b"-- a", 1), RETENTION, day(1)));
+        assert!(!history.record(version(b"-- a", 2), RETENTION, day(2)));
+        assert!(history.record(version(b"-- b", 3), RETENTION, day(3)));
+        assert!(
+            history.record(version(b"-- a", 4), RETENTION, day(4)),
+            "元に戻った場合は新しいバージョンとして記録する"
+        );
+
+        let published: Vec<_> = history
+            .versions()
+            .iter()
+            .map(|version| version.published_at)
+            .collect();
+        assert_eq!(published, [day(4), day(3), day(1)]);
+        assert_eq!(
+            history.get(&ContentHash::of(b"-- a")).unwrap().published_at,
+            day(4)
+        );
+    }
+
+    #[test]
+    fn versions_beyond_retention_are_removed() {
+        let mut history = DumpHistory::default();
+        for (index, dump) in [b"-- 1", b"-- 2", b"-- 3", b"-- 4"].into_iter().enumerate() {
+            history.record(version(dump, index as u32), RETENTION, day(index as u32));
+        }
+        assert_eq!(
+            history.versions().len(),
+            3,
+            "max_versions を超えた古いものは消える"
+        );
+        assert!(history.get(&ContentHash::of(b"-- 1")).is_none());
+
+        history.record(version(b"-- 4", 3), RETENTION, day(100));
+        assert_eq!(
+            history.versions().len(),
+            1,
+            "max_age を過ぎても公開中のバージョンは残る"
+        );
+        assert_eq!(history.versions()[0].content_hash, ContentHash::of(b"-- 4"));
+    }
+}
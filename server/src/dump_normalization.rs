@@ -0,0 +1,83 @@
+/// `mariadb-dump` が最後に書き出す行。日時を取り除いて完了の印としてだけ残す
+const DUMP_COMPLETED: &[u8] = b"-- Dump completed";
+
+/// 実行環境 (接続先やバージョン) を書いた行。データとは無関係なので取り除く
+const ENVIRONMENT_COMMENT_PREFIXES: &[&[u8]] = &[
+    // 接続先のホストとデータベース。フェイルオーバーや `MYSQL_HOST` の変更で変わる
+    b"-- Host: ",
+    // サーバーのバージョン (`mariadb-dump` と native dump の両方が書く)
+    b"-- Server version",
+    // `mariadb-dump` (クライアント) のバージョン
+    b"-- MariaDB dump ",
+    b"-- MySQL dump ",
+];
+
+/// 同じデータからは常に同じバイト列になるよう、実行ごとに変わる行を dump から取り除く
+///
+/// - `-- Dump completed on <日時>` は `-- Dump completed` に置き換える
+/// - 接続先のホストと、サーバー・クライアントのバージョンを書いたコメント行は削除する
+///
+/// それ以外の行 (`/*!40101 ... */` のような実行されるコメントを含む) はそのまま残す。
+pub fn normalize_dump(dump: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(dump.len());
+    for line in dump.split_inclusive(|&byte| byte == b'\n') {
+        if line.starts_with(DUMP_COMPLETED) {
+            normalized.extend_from_slice(DUMP_COMPLETED);
+            normalized.push(b'\n');
+        } else if !ENVIRONMENT_COMMENT_PREFIXES
+            .iter()
+            .any(|prefix| line.starts_with(prefix))
+        {
+            normalized.extend_from_slice(line);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_dump;
+
+    fn dump(host: &str, client: &str, server: &str, completed_on: &str) -> String {
+        format!(
+            "-- MariaDB dump 10.19  Distrib {client}, for debian-linux-gnu (x86_64)\n\
+             --\n\
+             -- Host: {host}    Database: seichiassist\n\
+             -- Server version\t{server}\n\
+             /*!40101 SET NAMES utf8mb4 */;\n\
+             INSERT INTO `gachadata` VALUES (1,0.5,'-- Server version');\n\
+             -- Dump completed on {completed_on}\n"
+        )
+    }
+
+    #[test]
+    fn same_data_is_normalized_to_same_bytes() {
+        let first = normalize_dump(
+            dump(
+                "db",
+                "10.11.6-MariaDB",
+                "10.11.6-MariaDB",
+                "2024-01-01 12:00:00",
+            )
+            .as_bytes(),
+        );
+        let second = normalize_dump(
+            dump(
+                "db-replica",
+                "11.4.2-MariaDB",
+                "11.4.3-MariaDB",
+                "2024-02-01 03:00:00",
+            )
+            .as_bytes(),
+        );
+
+        assert_eq!(first, second);
+        assert_eq!(
+            String::from_utf8(first).unwrap(),
+            "--\n\
+             /*!40101 SET NAMES utf8mb4 */;\n\
+             INSERT INTO `gachadata` VALUES (1,0.5,'-- Server version');\n\
+             -- Dump completed\n"
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use crate::domain::Shrinkage;
+use crate::sql_dump::parse_identifier;
+use std::collections::BTreeMap;
+
+/// `mariadb-dump` が最後に書き出す行の接頭辞。最後の行がこれでなければ途中で切れた dump とみなす
+const DUMP_COMPLETED_TRAILER: &str = "-- Dump completed";
+
+/// 検証済みの dump から読み取った統計
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpStats {
+    /// テーブルごとの行数 (`INSERT` 文に含まれる行の数)
+    pub row_counts: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum DumpValidationError {
+    #[error("dump has no CREATE TABLE statement for table `{0}`")]
+    MissingTable(String),
+    #[error("INSERT statement for table `{table}` at line {line} is truncated")]
+    TruncatedInsert { table: String, line: usize },
+    #[error("dump does not end with the \"{DUMP_COMPLETED_TRAILER}\" trailer")]
+    MissingTrailer,
+}
+
+/// dump が `tables` をすべて含む完全なものかを検証し、テーブルごとの行数を数える
+///
+/// `mariadb-dump` (と [`crate::native_dump`]) は `CREATE TABLE` と
+/// extended `INSERT` をそれぞれ行頭から書き、文字列中の改行はエスケープするため、
+/// 行単位で走査すれば文を取りこぼさない。
+/// BLOB 列はバイナリのまま書かれるため、UTF-8 としては扱わずバイト列のまま走査する。
+pub fn validate_dump(dump: &[u8], tables: &[String]) -> Result<DumpStats, DumpValidationError> {
+    let mut created = Vec::new();
+    let mut row_counts = BTreeMap::new();
+    let mut last_line: &[u8] = &[];
+    for (index, line) in dump.split(|&byte| byte == b'\n').enumerate() {
+        if let Some(rest) = line.strip_prefix(b"CREATE TABLE ") {
+            if let Some((table, _)) = parse_identifier(rest) {
+                row_counts.entry(table.clone()).or_insert(0);
+                created.push(table);
+            }
+        } else if let Some(rest) = line.strip_prefix(b"INSERT INTO ") {
+            let Some((table, values)) = parse_identifier(rest) else {
+                continue;
+            };
+            let rows = count_rows(values).ok_or_else(|| DumpValidationError::TruncatedInsert {
+                table: table.clone(),
+                line: index + 1,
+            })?;
+            *row_counts.entry(table).or_insert(0) += rows;
+        }
+        if !line.trim_ascii().is_empty() {
+            last_line = line;
+        }
+    }
+
+    if let Some(missing) = tables.iter().find(|table| !created.contains(table)) {
+        return Err(DumpValidationError::MissingTable(missing.clone()));
+    }
+    if !last_line.starts_with(DUMP_COMPLETED_TRAILER.as_bytes()) {
+        return Err(DumpValidationError::MissingTrailer);
+    }
+
+    row_counts.retain(|table, _| tables.contains(table));
+    Ok(DumpStats { row_counts })
+}
+
+/// 前回の dump と比べて、行数やサイズが `max_shrink_ratio` を超えて減ったものを返す
+///
+/// 例えば `max_shrink_ratio = 0.5` なら、前回の半分未満に減ったテーブル (またはサイズ) が
+/// 返る。前回 0 だったものは比較しない。
+pub fn detect_shrinkage(
+    previous_size: u64,
+    previous_row_counts: &BTreeMap<String, u64>,
+    current_size: u64,
+    current_row_counts: &BTreeMap<String, u64>,
+    max_shrink_ratio: f64,
+) -> Vec<Shrinkage> {
+    let shrunk_too_much =
+        |previous: u64, current: u64| (current as f64) < previous as f64 * (1.0 - max_shrink_ratio);
+
+    let mut shrinkage = Vec::new();
+    if shrunk_too_much(previous_size, current_size) {
+        shrinkage.push(Shrinkage {
+            subject: "size_bytes".to_owned(),
+            previous: previous_size,
+            current: current_size,
+        });
+    }
+    for (table, &previous) in previous_row_counts {
+        let current = current_row_counts.get(table).copied().unwrap_or(0);
+        if shrunk_too_much(previous, current) {
+            shrinkage.push(Shrinkage {
+                subject: format!("rows.{table}"),
+                previous,
+                current,
+            });
+        }
+    }
+    shrinkage
+}
+
+/// ` VALUES (...),(...);` の行数を数える。文が `;` で終わっていなければ `None`
+fn count_rows(values: &[u8]) -> Option<u64> {
+    let mut rows = 0;
+    let mut depth = 0u32;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut terminated = false;
+    for &byte in values {
+        if terminated && !byte.is_ascii_whitespace() {
+            // `;` の後に続きがある (1 行に複数の文があるのは想定外)
+            return None;
+        }
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'\'' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'\'' => in_string = true,
+            b'(' => {
+                if depth == 0 {
+                    rows += 1;
+                }
+                depth += 1;
+            }
+            b')' => depth = depth.checked_sub(1)?,
+            b';' if depth == 0 => terminated = true,
+            _ => {}
+        }
+    }
+
+    terminated.then_some(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DumpValidationError, detect_shrinkage, validate_dump};
+    use std::collections::BTreeMap;
+
+    fn tables() -> Vec<String> {
+        vec!["gachadata".to_owned(), "gacha_events".to_owned()]
+    }
+
+    const COMPLETE_DUMP: &str = "\
+-- MariaDB dump 10.19  Distrib 10.11.6-MariaDB, for debian-linux-gnu (x86_64)
+DROP TABLE IF EXISTS `gachadata`;
+CREATE TABLE `gachadata` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `probability` double NOT NULL,
+  `itemstack` blob DEFAULT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+LOCK TABLES `gachadata` WRITE;
+INSERT INTO `gachadata` VALUES (1,0.01,'it\\'s (not) a row'),(2,0.5,NULL),(3,0.1,'\\\\');
+INSERT INTO `gachadata` VALUES (4,0.2,'second statement');
+UNLOCK TABLES;
+DROP TABLE IF EXISTS `gacha_events`;
+CREATE TABLE `gacha_events` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+LOCK TABLES `gacha_events` WRITE;
+UNLOCK TABLES;
+
+-- Dump completed on 2024-01-01 12:00:00
+";
+
+    #[test]
+    fn counts_rows_per_table() {
+        let stats = validate_dump(COMPLETE_DUMP.as_bytes(), &tables()).unwrap();
+        assert_eq!(stats.row_counts["gachadata"], 4);
+        assert_eq!(
+            stats.row_counts["gacha_events"], 0,
+            "空のテーブルも 0 行として数える"
+        );
+    }
+
+    #[test]
+    fn binary_blob_values_are_scanned_as_bytes() {
+        let mut dump = COMPLETE_DUMP.as_bytes().to_vec();
+        let at = COMPLETE_DUMP.find("second statement").unwrap();
+        dump.splice(at..at, [0xac, 0xed, 0x00, 0x05, b'(']);
+
+        let stats = validate_dump(&dump, &tables()).unwrap();
+        assert_eq!(stats.row_counts["gachadata"], 4);
+    }
+
+    fn row_counts(gachadata: u64, gacha_events: u64) -> BTreeMap<String, u64> {
+        BTreeMap::from([
+            ("gachadata".to_owned(), gachadata),
+            ("gacha_events".to_owned(), gacha_events),
+        ])
+    }
+
+    #[test]
+    fn shrinkage_beyond_ratio_is_detected() {
+        let shrinkage = detect_shrinkage(
+            100_000,
+            &row_counts(1000, 10),
+            2_000,
+            &row_counts(0, 10),
+            0.5,
+        );
+
+        let subjects: Vec<_> = shrinkage.iter().map(|s| s.subject.as_str()).collect();
+        assert_eq!(subjects, ["size_bytes", "rows.gachadata"]);
+        assert_eq!(shrinkage[1].previous, 1000);
+        assert_eq!(shrinkage[1].current, 0);
+    }
+
+    #[test]
+    fn shrinkage_within_ratio_or_growth_is_allowed() {
+        assert!(
+            detect_shrinkage(
+                100_000,
+                &row_counts(1000, 10),
+                60_000,
+                &row_counts(600, 5),
+                0.5
+            )
+            .is_empty()
+        );
+        assert!(
+            detect_shrinkage(
+                100_000,
+                &row_counts(0, 0),
+                200_000,
+                &row_counts(2000, 20),
+                0.5
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn rejects_dump_missing_a_table() {
+        let dump = COMPLETE_DUMP.replace("CREATE TABLE `gacha_events`", "-- removed");
+        assert_eq!(
+            validate_dump(dump.as_bytes(), &tables()),
+            Err(DumpValidationError::MissingTable("gacha_events".to_owned()))
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_dump() {
+        let cut = COMPLETE_DUMP.find("(3,0.1").unwrap();
+        assert_eq!(
+            validate_dump(&COMPLETE_DUMP.as_bytes()[..cut], &tables()),
+            Err(DumpValidationError::TruncatedInsert {
+                table: "gachadata".to_owned(),
+                line: 10
+            })
+        );
+
+        let without_trailer = COMPLETE_DUMP.replace("-- Dump completed", "--");
+        assert_eq!(
+            validate_dump(without_trailer.as_bytes(), &tables()),
+            Err(DumpValidationError::MissingTrailer)
+        );
+    }
+
+    #[test]
+    fn rejects_dump_with_statements_after_the_trailer() {
+        // 2 つの dump をつなげたものや、途中に完了の行が紛れ込んだまま切れたもの
+        let continued = format!("{COMPLETE_DUMP}INSERT INTO `gachadata` VALUES (5,0.1);\n");
+        assert_eq!(
+            validate_dump(continued.as_bytes(), &tables()),
+            Err(DumpValidationError::MissingTrailer)
+        );
+
+        let with_blank_lines = format!("{COMPLETE_DUMP}\n\n");
+        assert!(validate_dump(with_blank_lines.as_bytes(), &tables()).is_ok());
+    }
+}
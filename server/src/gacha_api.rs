@@ -0,0 +1,571 @@
+use crate::config::Gacha;
+use crate::domain::{GachaData, GachaDataRepository, GachaEvent, GachaPrize, ItemStack};
+use crate::item_stack::plain_text;
+use crate::presentation::{published_gacha, versioned_json};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{ErrorResponse, IntoResponse, Response, Result};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Debug, Default, Deserialize)]
+pub enum PrizeSort {
+    /// `gachadata.id` 順 (dump に書かれた順)
+    #[default]
+    #[serde(rename = "id")]
+    Id,
+    /// 確率の低い順
+    #[serde(rename = "probability")]
+    Probability,
+    /// 確率の高い順
+    #[serde(rename = "-probability")]
+    ProbabilityDescending,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PrizesQuery {
+    /// イベント名。空文字なら通常のガチャ (イベントに属さない) の景品だけを返す
+    event: Option<String>,
+    #[serde(default)]
+    sort: PrizeSort,
+}
+
+/// フィールド名は `schemas/v1/prizes.schema.json` で公開しているため変えないこと
+#[derive(Serialize)]
+pub struct PrizesResponse {
+    version: String,
+    prizes: Vec<PrizeResponse>,
+}
+
+#[derive(Serialize)]
+pub struct PrizeResponse {
+    id: i32,
+    probability: f64,
+    event_id: Option<i32>,
+    event_name: Option<String>,
+    itemstack_base64: Option<String>,
+    item: Option<ItemResponse>,
+}
+
+/// 表示名と説明文は装飾を除いた文字列にする
+#[derive(Serialize)]
+pub struct ItemResponse {
+    material: String,
+    amount: i32,
+    display_name: Option<String>,
+    lore: Vec<String>,
+    enchantments: BTreeMap<String, i32>,
+    custom_model_data: Option<i32>,
+}
+
+fn item_response(item: &ItemStack) -> ItemResponse {
+    ItemResponse {
+        material: item.material.clone(),
+        amount: item.amount,
+        display_name: item.display_name.as_deref().map(plain_text),
+        lore: item.lore.iter().map(|line| plain_text(line)).collect(),
+        enchantments: item.enchantments.clone(),
+        custom_model_data: item.custom_model_data,
+    }
+}
+
+fn prize_response(prize: &GachaPrize, gacha: &GachaData) -> PrizeResponse {
+    let event_name = prize.event_id.and_then(|event_id| {
+        gacha
+            .events
+            .iter()
+            .find(|event| event.id == event_id)
+            .map(|event| event.name.clone())
+    });
+    PrizeResponse {
+        id: prize.id,
+        probability: prize.probability,
+        event_id: prize.event_id,
+        event_name,
+        itemstack_base64: prize
+            .itemstack
+            .as_ref()
+            .map(|itemstack| BASE64_STANDARD.encode(itemstack)),
+        item: prize.item.as_ref().map(item_response),
+    }
+}
+
+/// `?event=` による景品の絞り込み
+#[derive(Clone, Copy)]
+enum PrizeFilter {
+    All,
+    /// `gacha_events.id` がこれと一致する景品 (`None` なら通常のガチャの景品)
+    Event(Option<i32>),
+}
+
+impl PrizeFilter {
+    /// 空のイベント名なら通常のガチャの景品。存在しないイベント名なら `None`
+    fn from_query(gacha: &GachaData, event: Option<&str>) -> Option<Self> {
+        match event {
+            None => Some(PrizeFilter::All),
+            Some(event) => SingleGacha::from_query(gacha, Some(event))
+                .map(|single| PrizeFilter::Event(single.event_id)),
+        }
+    }
+
+    fn matches(self, prize: &GachaPrize) -> bool {
+        match self {
+            PrizeFilter::All => true,
+            PrizeFilter::Event(event_id) => prize.event_id == event_id,
+        }
+    }
+}
+
+/// 1 回引くときに景品を選ぶ範囲 (通常のガチャか、1 つのイベントのガチャ)
+///
+/// イベントごとに確率の合計が 1 以下になるよう決めてあるため、ガチャをまたいで景品を混ぜると
+/// 実際のどのガチャとも確率が合わなくなる。
+#[derive(Clone, Copy)]
+pub struct SingleGacha {
+    /// `gacha_events.id`。通常のガチャなら `None`
+    pub event_id: Option<i32>,
+}
+
+impl SingleGacha {
+    /// イベント名を省略するか空文字なら通常のガチャ。存在しないイベント名なら `None`
+    pub fn from_query(gacha: &GachaData, event: Option<&str>) -> Option<Self> {
+        match event {
+            None | Some("") => Some(SingleGacha { event_id: None }),
+            Some(name) => gacha
+                .events
+                .iter()
+                .find(|event| event.name == name)
+                .map(|event| SingleGacha {
+                    event_id: Some(event.id),
+                }),
+        }
+    }
+
+    pub fn prizes(self, gacha: &GachaData) -> Vec<&GachaPrize> {
+        gacha
+            .prizes
+            .iter()
+            .filter(|prize| prize.event_id == self.event_id)
+            .collect()
+    }
+
+    /// ダウンロードするファイルやルートテーブルの名前 (拡張子なし)
+    pub fn file_stem(self) -> String {
+        match self.event_id {
+            None => "gacha-regular".to_owned(),
+            Some(event_id) => format!("gacha-event-{event_id}"),
+        }
+    }
+}
+
+pub fn no_such_event() -> ErrorResponse {
+    ErrorResponse::from(
+        (StatusCode::NOT_FOUND, "No gacha event with the given name.").into_response(),
+    )
+}
+
+/// 公開中の dump の景品を JSON で返す
+#[tracing::instrument(skip(repository, request_headers))]
+pub async fn get_prizes_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    Query(query): Query<PrizesQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    let (version, gacha) = published_gacha(&repository).await?;
+
+    let filter =
+        PrizeFilter::from_query(&gacha, query.event.as_deref()).ok_or_else(no_such_event)?;
+    let mut prizes: Vec<_> = gacha
+        .prizes
+        .iter()
+        .filter(|prize| filter.matches(prize))
+        .collect();
+    match query.sort {
+        PrizeSort::Id => {}
+        PrizeSort::Probability => {
+            prizes.sort_by(|a, b| a.probability.total_cmp(&b.probability));
+        }
+        PrizeSort::ProbabilityDescending => {
+            prizes.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+        }
+    }
+
+    let prizes = prizes
+        .into_iter()
+        .map(|prize| prize_response(prize, &gacha))
+        .collect();
+
+    Ok(versioned_json(
+        &version,
+        &request_headers,
+        PrizesResponse {
+            version: version.0.clone(),
+            prizes,
+        },
+    ))
+}
+
+/// `GET /api/v1/prizes` の JSON Schema
+pub const PRIZES_SCHEMA: &str = include_str!("../schemas/v1/prizes.schema.json");
+
+pub async fn get_prizes_schema_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/schema+json")],
+        PRIZES_SCHEMA,
+    )
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsQuery {
+    /// この日時に開催中のイベントだけを返す (RFC 3339 または UNIX 時間の秒)
+    active_at: Option<String>,
+}
+
+/// フィールド名は `schemas/v1/events.schema.json` で公開しているため変えないこと
+#[derive(Serialize)]
+pub struct EventsResponse {
+    version: String,
+    events: Vec<EventResponse>,
+}
+
+#[derive(Serialize)]
+pub struct SingleEventResponse {
+    version: String,
+    event: EventResponse,
+}
+
+#[derive(Serialize)]
+pub struct EventResponse {
+    id: i32,
+    name: String,
+    /// 開始日時 (RFC 3339、`GACHA_EVENT_UTC_OFFSET_HOURS` のオフセット付き)
+    start_time: String,
+    /// 終了日時 (RFC 3339)。この日時ちょうどまでは開催中
+    end_time: String,
+    prizes: Vec<PrizeResponse>,
+}
+
+/// `gacha_events` の日時を、記録しているタイムゾーンでの時刻として解釈する
+fn event_timestamp(time: jiff::civil::DateTime, gacha: &Gacha) -> Option<jiff::Timestamp> {
+    time.to_zoned(gacha.event_time_zone())
+        .ok()
+        .map(|zoned| zoned.timestamp())
+}
+
+fn format_event_time(time: jiff::civil::DateTime, gacha: &Gacha) -> String {
+    time.to_zoned(gacha.event_time_zone())
+        .map(|zoned| zoned.strftime("%Y-%m-%dT%H:%M:%S%:z").to_string())
+        .unwrap_or_else(|_| time.to_string())
+}
+
+fn is_active_at(event: &GachaEvent, at: jiff::Timestamp, gacha: &Gacha) -> bool {
+    let (Some(start), Some(end)) = (
+        event_timestamp(event.start_time, gacha),
+        event_timestamp(event.end_time, gacha),
+    ) else {
+        return false;
+    };
+    start <= at && at <= end
+}
+
+/// RFC 3339 の日時または UNIX 時間の秒を読む
+fn parse_active_at(active_at: &str) -> Option<jiff::Timestamp> {
+    match active_at.parse::<i64>() {
+        Ok(unix_secs) => jiff::Timestamp::from_second(unix_secs).ok(),
+        Err(_) => active_at.parse::<jiff::Timestamp>().ok(),
+    }
+}
+
+fn event_response(event: &GachaEvent, gacha: &GachaData, settings: &Gacha) -> EventResponse {
+    EventResponse {
+        id: event.id,
+        name: event.name.clone(),
+        start_time: format_event_time(event.start_time, settings),
+        end_time: format_event_time(event.end_time, settings),
+        prizes: gacha
+            .prizes
+            .iter()
+            .filter(|prize| prize.event_id == Some(event.id))
+            .map(|prize| prize_response(prize, gacha))
+            .collect(),
+    }
+}
+
+/// 公開中の dump のガチャイベントを、それぞれの景品と一緒に JSON で返す
+#[tracing::instrument(skip(repository, settings, request_headers))]
+pub async fn get_events_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    State(settings): State<Arc<Gacha>>,
+    Query(query): Query<EventsQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    let active_at = match query.active_at.as_deref() {
+        None => None,
+        Some(active_at) => Some(parse_active_at(active_at).ok_or_else(|| {
+            ErrorResponse::from(
+                (
+                    StatusCode::BAD_REQUEST,
+                    "active_at must be an RFC 3339 timestamp or UNIX seconds.",
+                )
+                    .into_response(),
+            )
+        })?),
+    };
+    let (version, gacha) = published_gacha(&repository).await?;
+
+    let events = gacha
+        .events
+        .iter()
+        .filter(|event| active_at.is_none_or(|at| is_active_at(event, at, &settings)))
+        .map(|event| event_response(event, &gacha, &settings))
+        .collect();
+
+    Ok(versioned_json(
+        &version,
+        &request_headers,
+        EventsResponse {
+            version: version.0.clone(),
+            events,
+        },
+    ))
+}
+
+/// 名前が `name` のガチャイベントを JSON で返す
+#[tracing::instrument(skip(repository, settings, request_headers))]
+pub async fn get_event_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    State(settings): State<Arc<Gacha>>,
+    Path(name): Path<String>,
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    let (version, gacha) = published_gacha(&repository).await?;
+    let event = gacha
+        .events
+        .iter()
+        .find(|event| event.name == name)
+        .ok_or_else(no_such_event)?;
+
+    Ok(versioned_json(
+        &version,
+        &request_headers,
+        SingleEventResponse {
+            version: version.0.clone(),
+            event: event_response(event, &gacha, &settings),
+        },
+    ))
+}
+
+/// `GET /api/v1/events` の JSON Schema
+pub const EVENTS_SCHEMA: &str = include_str!("../schemas/v1/events.schema.json");
+
+pub async fn get_events_schema_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/schema+json")],
+        EVENTS_SCHEMA,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EventsQuery, PRIZES_SCHEMA, PrizeSort, PrizesQuery, get_event_handler, get_events_handler,
+        get_prizes_handler,
+    };
+    use crate::presentation::test_support::{gacha_repository, gacha_settings, json_body};
+    use axum::extract::{Path, Query, State};
+    use axum::http::{HeaderMap, StatusCode, header};
+    use axum::response::IntoResponse;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn prizes_are_filtered_by_event_and_sorted_by_probability() {
+        let repository = gacha_repository();
+        let prize_ids = |query: PrizesQuery| {
+            let repository = Arc::clone(&repository);
+            async move {
+                let response =
+                    get_prizes_handler(State(repository), Query(query), HeaderMap::new())
+                        .await
+                        .unwrap();
+                let json = json_body(response).await;
+                json["prizes"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|prize| prize["id"].as_i64().unwrap())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(prize_ids(PrizesQuery::default()).await, [1, 2, 3, 4]);
+        assert_eq!(
+            prize_ids(PrizesQuery {
+                event: Some("正月".to_owned()),
+                sort: PrizeSort::ProbabilityDescending,
+            })
+            .await,
+            [4, 2]
+        );
+        assert_eq!(
+            prize_ids(PrizesQuery {
+                event: Some(String::new()),
+                sort: PrizeSort::Probability,
+            })
+            .await,
+            [1, 3],
+            "空のイベント名は通常のガチャの景品"
+        );
+
+        let unknown = get_prizes_handler(
+            State(repository),
+            Query(PrizesQuery {
+                event: Some("夏祭り".to_owned()),
+                ..PrizesQuery::default()
+            }),
+            HeaderMap::new(),
+        )
+        .await;
+        assert_eq!(unknown.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn prizes_response_follows_published_schema() {
+        let repository = gacha_repository();
+        let response = get_prizes_handler(
+            State(Arc::clone(&repository)),
+            Query(PrizesQuery::default()),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let etag = response.headers()[header::ETAG].clone();
+        let json = json_body(response).await;
+
+        let schema: serde_json::Value = serde_json::from_str(PRIZES_SCHEMA).unwrap();
+        let keys = |object: &serde_json::Value| {
+            let mut keys: Vec<_> = object.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let required = |schema: &serde_json::Value| {
+            let mut required: Vec<_> = schema["required"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|key| key.as_str().unwrap().to_owned())
+                .collect();
+            required.sort();
+            required
+        };
+        assert_eq!(keys(&json), required(&schema));
+        assert_eq!(
+            keys(&json["prizes"][1]),
+            required(&schema["$defs"]["prize"])
+        );
+        assert_eq!(json["prizes"][1]["event_name"], "正月");
+        assert_eq!(json["prizes"][1]["itemstack_base64"], "rO0=");
+        assert_eq!(
+            keys(&json["prizes"][1]["item"]),
+            required(&schema["$defs"]["item"])
+        );
+        assert_eq!(json["prizes"][1]["item"]["display_name"], "すごいダイヤ");
+        assert_eq!(json["prizes"][1]["item"]["lore"][0], "景品 #2");
+        assert_eq!(json["prizes"][1]["item"]["enchantments"]["DURABILITY"], 3);
+
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(header::IF_NONE_MATCH, etag);
+        let response = get_prizes_handler(
+            State(repository),
+            Query(PrizesQuery::default()),
+            request_headers,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn events_active_at_given_time_are_listed_with_their_prizes() {
+        let repository = gacha_repository();
+        let events = |active_at: Option<&str>| {
+            let repository = Arc::clone(&repository);
+            let query = EventsQuery {
+                active_at: active_at.map(str::to_owned),
+            };
+            async move {
+                get_events_handler(
+                    State(repository),
+                    State(gacha_settings()),
+                    Query(query),
+                    HeaderMap::new(),
+                )
+                .await
+                .map(|response| async { json_body(response).await["events"].clone() })
+            }
+        };
+
+        let all = events(None).await.unwrap().await;
+        assert_eq!(all[0]["name"], "正月");
+        assert_eq!(all[0]["start_time"], "2024-01-01T00:00:00+09:00");
+        assert_eq!(all[0]["end_time"], "2024-01-07T23:59:59+09:00");
+        let prize_ids: Vec<_> = all[0]["prizes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|prize| prize["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(prize_ids, [2, 4]);
+
+        let active_count = |active_at: &'static str| {
+            let events = &events;
+            async move {
+                events(Some(active_at))
+                    .await
+                    .unwrap()
+                    .await
+                    .as_array()
+                    .unwrap()
+                    .len()
+            }
+        };
+        assert_eq!(active_count("2024-01-01T00:00:00+09:00").await, 1);
+        assert_eq!(active_count("2023-12-31T23:59:59+09:00").await, 0);
+        assert_eq!(active_count("2024-01-07T14:59:59Z").await, 1);
+        assert_eq!(
+            active_count("1704639600").await,
+            0,
+            "2024-01-08T00:00:00+09:00 には終わっている"
+        );
+
+        let invalid = events(Some("tomorrow")).await.map(|_| ());
+        assert_eq!(invalid.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn single_event_is_found_by_name() {
+        let repository = gacha_repository();
+        let response = get_event_handler(
+            State(Arc::clone(&repository)),
+            State(gacha_settings()),
+            Path("正月".to_owned()),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let json = json_body(response).await;
+        assert_eq!(json["event"]["id"], 1);
+        assert_eq!(json["event"]["prizes"].as_array().unwrap().len(), 2);
+
+        let missing = get_event_handler(
+            State(repository),
+            State(gacha_settings()),
+            Path("夏祭り".to_owned()),
+            HeaderMap::new(),
+        )
+        .await;
+        assert_eq!(missing.into_response().status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::domain::{GachaData, GachaPrize, ItemStack};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// 確率の合計が 1 を超えたとみなす誤差。`DOUBLE` の確率を足し合わせた丸め誤差は許す
+const PROBABILITY_SUM_TOLERANCE: f64 = 1e-9;
+
+/// 検査の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    /// 通常のガチャまたはイベントごとの確率の合計が 1 を超えている
+    ProbabilitySum,
+    /// 同じガチャに同じアイテムの景品が複数ある
+    DuplicateItem,
+    /// 景品の `event_id` に対応する `gacha_events` の行がない
+    OrphanEventReference,
+    /// イベントの終了日時が開始日時より前
+    EventEndsBeforeStart,
+}
+
+/// 検査で見つかった問題
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LintFinding {
+    pub rule: LintRule,
+    pub message: String,
+    /// 問題のあるイベント、または問題のある景品が属するイベント (`gacha_events.id`)
+    pub event_id: Option<i32>,
+    /// 問題のある景品 (`gachadata.id`)
+    pub prize_ids: Vec<i32>,
+}
+
+/// dump から読み取った景品とイベントを検査する
+///
+/// 景品は通常のガチャ (`event_id` が `NULL`) とイベント (`event_id` ごと) に分けて検査する。
+/// SeichiAssist はどの景品にも当たらなかった分をはずれとするため、確率の合計が 1 未満なのは問題としない。
+/// 見つかった問題はイベント、景品の順に並べる。
+pub fn lint(gacha: &GachaData) -> Vec<LintFinding> {
+    let mut pools: BTreeMap<Option<i32>, Vec<&GachaPrize>> = BTreeMap::new();
+    for prize in &gacha.prizes {
+        pools.entry(prize.event_id).or_default().push(prize);
+    }
+
+    let mut findings = Vec::new();
+    for event in &gacha.events {
+        if event.end_time < event.start_time {
+            findings.push(LintFinding {
+                rule: LintRule::EventEndsBeforeStart,
+                message: format!(
+                    "イベント「{}」の終了日時 ({}) が開始日時 ({}) より前です",
+                    event.name, event.end_time, event.start_time
+                ),
+                event_id: Some(event.id),
+                prize_ids: Vec::new(),
+            });
+        }
+    }
+    for (&event_id, prizes) in &pools {
+        let pool_name = match event_id {
+            None => "通常のガチャ".to_owned(),
+            Some(event_id) => format!("イベント {event_id}"),
+        };
+        let prize_ids = || prizes.iter().map(|prize| prize.id).collect();
+
+        if let Some(event_id) = event_id
+            && !gacha.events.iter().any(|event| event.id == event_id)
+        {
+            findings.push(LintFinding {
+                rule: LintRule::OrphanEventReference,
+                message: format!(
+                    "景品が参照しているイベント {event_id} が gacha_events にありません"
+                ),
+                event_id: Some(event_id),
+                prize_ids: prize_ids(),
+            });
+        }
+
+        let sum: f64 = prizes.iter().map(|prize| prize.probability).sum();
+        if sum > 1.0 + PROBABILITY_SUM_TOLERANCE {
+            findings.push(LintFinding {
+                rule: LintRule::ProbabilitySum,
+                message: format!("{pool_name}の景品の確率の合計 ({sum}) が 1 を超えています"),
+                event_id,
+                prize_ids: prize_ids(),
+            });
+        }
+
+        for duplicates in duplicate_items(prizes) {
+            findings.push(LintFinding {
+                rule: LintRule::DuplicateItem,
+                message: format!("{pool_name}に同じアイテムの景品が複数あります"),
+                event_id,
+                prize_ids: duplicates,
+            });
+        }
+    }
+    findings
+}
+
+/// 同じアイテムの景品の ID を、同じものごとにまとめて返す
+///
+/// 読み取れたアイテムは中身で、読み取れなかったものは `itemstack` のバイト列で比べる。
+fn duplicate_items(prizes: &[&GachaPrize]) -> Vec<Vec<i32>> {
+    #[derive(PartialEq)]
+    enum Item<'a> {
+        Decoded(&'a ItemStack),
+        Raw(&'a [u8]),
+    }
+
+    let mut groups: Vec<(Item, Vec<i32>)> = Vec::new();
+    for prize in prizes {
+        let item = match (&prize.item, &prize.itemstack) {
+            (Some(item), _) => Item::Decoded(item),
+            (None, Some(itemstack)) => Item::Raw(itemstack),
+            (None, None) => continue,
+        };
+        match groups.iter_mut().find(|(other, _)| *other == item) {
+            Some((_, ids)) => ids.push(prize.id),
+            None => groups.push((item, vec![prize.id])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, ids)| ids)
+        .filter(|ids| ids.len() > 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LintRule, lint};
+    use crate::domain::{GachaData, GachaEvent, GachaPrize, ItemStack};
+    use bytes::Bytes;
+    use std::collections::BTreeMap;
+
+    fn prize(id: i32, probability: f64, material: &str, event_id: Option<i32>) -> GachaPrize {
+        GachaPrize {
+            id,
+            probability,
+            itemstack: Some(Bytes::from(material.to_owned())),
+            item: Some(ItemStack {
+                material: material.to_owned(),
+                amount: 1,
+                damage: 0,
+                display_name: None,
+                lore: Vec::new(),
+                enchantments: BTreeMap::new(),
+                custom_model_data: None,
+            }),
+            event_id,
+        }
+    }
+
+    fn event(id: i32, start_time: &str, end_time: &str) -> GachaEvent {
+        GachaEvent {
+            id,
+            name: format!("イベント{id}"),
+            start_time: start_time.parse().unwrap(),
+            end_time: end_time.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn consistent_gacha_has_no_findings() {
+        let gacha = GachaData {
+            prizes: vec![
+                prize(1, 0.1, "DIAMOND", None),
+                prize(2, 0.9, "STONE", None),
+                // イベントごとに別のガチャなので、通常のガチャと同じアイテムでもよい
+                prize(3, 0.5, "DIAMOND", Some(1)),
+                // 0.1 + 0.2 + 0.7 は丸め誤差で 1 をわずかに超える
+                prize(4, 0.1, "APPLE", Some(2)),
+                prize(5, 0.2, "BREAD", Some(2)),
+                prize(6, 0.7, "CAKE", Some(2)),
+            ],
+            events: vec![
+                event(1, "2024-01-01T00:00:00", "2024-01-07T23:59:59"),
+                event(2, "2024-02-01T00:00:00", "2024-02-01T00:00:00"),
+            ],
+        };
+        assert_eq!(lint(&gacha), []);
+    }
+
+    #[test]
+    fn each_rule_reports_the_offending_prizes_and_events() {
+        let mut undecodable = prize(5, 0.1, "RAW", Some(1));
+        undecodable.item = None;
+        let mut same_bytes = undecodable.clone();
+        same_bytes.id = 6;
+        let gacha = GachaData {
+            prizes: vec![
+                prize(1, 0.6, "DIAMOND", None),
+                prize(2, 0.6, "DIAMOND", None),
+                prize(3, 0.1, "STONE", Some(1)),
+                prize(4, 0.1, "STONE", Some(9)),
+                undecodable,
+                same_bytes,
+            ],
+            events: vec![event(1, "2024-01-07T00:00:00", "2024-01-01T00:00:00")],
+        };
+
+        let findings: Vec<_> = lint(&gacha)
+            .into_iter()
+            .map(|finding| (finding.rule, finding.event_id, finding.prize_ids))
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                (LintRule::EventEndsBeforeStart, Some(1), vec![]),
+                (LintRule::ProbabilitySum, None, vec![1, 2]),
+                (LintRule::DuplicateItem, None, vec![1, 2]),
+                (LintRule::DuplicateItem, Some(1), vec![5, 6]),
+                (LintRule::OrphanEventReference, Some(9), vec![4]),
+            ]
+        );
+    }
+}
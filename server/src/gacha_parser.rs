@@ -0,0 +1,383 @@
+use crate::domain::{GachaData, GachaEvent, GachaPrize};
+use crate::item_stack::decode_item_stack;
+use crate::sql_dump::{Location, SqlDumpParseError, SqlValue, Table, parse_dump};
+use bytes::Bytes;
+
+const GACHADATA: &str = "gachadata";
+const GACHA_EVENTS: &str = "gacha_events";
+
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+pub enum GachaDataParseError {
+    #[error("failed to parse dump: {0}")]
+    Syntax(#[from] SqlDumpParseError),
+    #[error("dump has no CREATE TABLE statement for table `{0}`")]
+    MissingTable(&'static str),
+    #[error("table `{table}` has no column `{column}`")]
+    MissingColumn {
+        table: &'static str,
+        column: &'static str,
+    },
+    #[error("line {}, column {}: `{table}`.`{column}` {reason}", location.line, location.column)]
+    InvalidValue {
+        table: &'static str,
+        column: &'static str,
+        /// 値を含む行の `(` の位置
+        location: Location,
+        reason: String,
+    },
+}
+
+/// dump から読み取った景品とイベント
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedGachaData {
+    pub gacha: GachaData,
+    /// 値が不正なため読み飛ばした行 (いずれも [`GachaDataParseError::InvalidValue`])
+    pub skipped_rows: Vec<GachaDataParseError>,
+}
+
+/// dump の `gachadata` と `gacha_events` の `INSERT` 文を読み、景品とイベントにする
+///
+/// `gacha_events` がない dump (イベント機能より前の SeichiAssist) ではイベントを空とし、
+/// 同じく `gachadata.event_id` がなければすべて通常のガチャの景品とする。
+/// `gachadata.itemstack` を読み取れない景品は、dump 全体をエラーにせず `item` を `None` とする。
+/// 同じく `NULL` の確率や `0000-00-00` の日時のような不正な値を含む行は読み飛ばし、
+/// その位置を `skipped_rows` に残す。
+pub fn parse_gacha_data(dump: &[u8]) -> Result<ParsedGachaData, GachaDataParseError> {
+    let tables = parse_dump(dump)?;
+    let gachadata = tables
+        .get(GACHADATA)
+        .ok_or(GachaDataParseError::MissingTable(GACHADATA))?;
+    let mut skipped_rows = Vec::new();
+    let prizes = parse_prizes(gachadata, &mut skipped_rows)?;
+    let events = match tables.get(GACHA_EVENTS) {
+        Some(gacha_events) => parse_events(gacha_events, &mut skipped_rows)?,
+        None => Vec::new(),
+    };
+    Ok(ParsedGachaData {
+        gacha: GachaData { prizes, events },
+        skipped_rows,
+    })
+}
+
+fn parse_prizes(
+    table: &Table,
+    skipped_rows: &mut Vec<GachaDataParseError>,
+) -> Result<Vec<GachaPrize>, GachaDataParseError> {
+    let id = Column::required(table, GACHADATA, "id")?;
+    let probability = Column::required(table, GACHADATA, "probability")?;
+    let itemstack = Column::required(table, GACHADATA, "itemstack")?;
+    let event_id = Column::optional(table, GACHADATA, "event_id");
+
+    Ok(parse_rows(table, skipped_rows, |row| {
+        let itemstack = row.nullable_bytes(&itemstack)?;
+        Ok(GachaPrize {
+            id: row.integer(&id)?,
+            probability: row.float(&probability)?,
+            item: itemstack
+                .as_deref()
+                .and_then(|itemstack| decode_item_stack(itemstack).ok()),
+            itemstack,
+            event_id: match &event_id {
+                Some(event_id) => row.nullable_integer(event_id)?,
+                None => None,
+            },
+        })
+    }))
+}
+
+fn parse_events(
+    table: &Table,
+    skipped_rows: &mut Vec<GachaDataParseError>,
+) -> Result<Vec<GachaEvent>, GachaDataParseError> {
+    let id = Column::required(table, GACHA_EVENTS, "id")?;
+    let name = Column::required(table, GACHA_EVENTS, "event_name")?;
+    let start_time = Column::required(table, GACHA_EVENTS, "event_start_time")?;
+    let end_time = Column::required(table, GACHA_EVENTS, "event_end_time")?;
+
+    Ok(parse_rows(table, skipped_rows, |row| {
+        Ok(GachaEvent {
+            id: row.integer(&id)?,
+            name: row.string(&name)?,
+            start_time: row.datetime(&start_time)?,
+            end_time: row.datetime(&end_time)?,
+        })
+    }))
+}
+
+/// 各行を `parse` で読み、読めなかった行はエラーを `skipped_rows` に残して読み飛ばす
+fn parse_rows<T>(
+    table: &Table,
+    skipped_rows: &mut Vec<GachaDataParseError>,
+    parse: impl Fn(&Row) -> Result<T, GachaDataParseError>,
+) -> Vec<T> {
+    rows(table)
+        .filter_map(|row| parse(&row).map_err(|error| skipped_rows.push(error)).ok())
+        .collect()
+}
+
+/// 列名と、行の中でのその列の位置
+struct Column {
+    table: &'static str,
+    name: &'static str,
+    index: usize,
+}
+
+impl Column {
+    fn optional(table: &Table, table_name: &'static str, name: &'static str) -> Option<Self> {
+        let index = table.columns.iter().position(|column| column == name)?;
+        Some(Column {
+            table: table_name,
+            name,
+            index,
+        })
+    }
+
+    fn required(
+        table: &Table,
+        table_name: &'static str,
+        name: &'static str,
+    ) -> Result<Self, GachaDataParseError> {
+        Column::optional(table, table_name, name).ok_or(GachaDataParseError::MissingColumn {
+            table: table_name,
+            column: name,
+        })
+    }
+}
+
+fn rows(table: &Table) -> impl Iterator<Item = Row<'_>> {
+    table
+        .rows
+        .iter()
+        .zip(&table.row_locations)
+        .map(|(values, &location)| Row { values, location })
+}
+
+struct Row<'a> {
+    values: &'a [SqlValue],
+    location: Location,
+}
+
+impl Row<'_> {
+    fn invalid(&self, column: &Column, reason: impl Into<String>) -> GachaDataParseError {
+        GachaDataParseError::InvalidValue {
+            table: column.table,
+            column: column.name,
+            location: self.location,
+            reason: reason.into(),
+        }
+    }
+
+    fn value(&self, column: &Column) -> &SqlValue {
+        &self.values[column.index]
+    }
+
+    fn non_null(&self, column: &Column) -> Result<&SqlValue, GachaDataParseError> {
+        match self.value(column) {
+            SqlValue::Null => Err(self.invalid(column, "must not be NULL")),
+            value => Ok(value),
+        }
+    }
+
+    fn number(&self, column: &Column) -> Result<&str, GachaDataParseError> {
+        match self.non_null(column)? {
+            SqlValue::Number(number) => Ok(number),
+            value => Err(self.invalid(column, format!("must be a number, but was {value}"))),
+        }
+    }
+
+    fn integer(&self, column: &Column) -> Result<i32, GachaDataParseError> {
+        let number = self.number(column)?;
+        number
+            .parse()
+            .map_err(|_| self.invalid(column, format!("must be an integer, but was {number}")))
+    }
+
+    fn nullable_integer(&self, column: &Column) -> Result<Option<i32>, GachaDataParseError> {
+        match self.value(column) {
+            SqlValue::Null => Ok(None),
+            _ => self.integer(column).map(Some),
+        }
+    }
+
+    fn float(&self, column: &Column) -> Result<f64, GachaDataParseError> {
+        let number = self.number(column)?;
+        number
+            .parse()
+            .map_err(|_| self.invalid(column, format!("must be a number, but was {number}")))
+    }
+
+    fn nullable_bytes(&self, column: &Column) -> Result<Option<Bytes>, GachaDataParseError> {
+        match self.value(column) {
+            SqlValue::Null => Ok(None),
+            SqlValue::String(bytes) => Ok(Some(Bytes::copy_from_slice(bytes))),
+            value => Err(self.invalid(column, format!("must be a BLOB, but was {value}"))),
+        }
+    }
+
+    fn string(&self, column: &Column) -> Result<String, GachaDataParseError> {
+        match self.non_null(column)? {
+            SqlValue::String(bytes) => String::from_utf8(bytes.clone())
+                .map_err(|_| self.invalid(column, "is not valid UTF-8")),
+            value => Err(self.invalid(column, format!("must be a string, but was {value}"))),
+        }
+    }
+
+    fn datetime(&self, column: &Column) -> Result<jiff::civil::DateTime, GachaDataParseError> {
+        let text = self.string(column)?;
+        text.parse().map_err(|error| {
+            self.invalid(
+                column,
+                format!("must be a DATETIME, but was '{text}': {error}"),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GachaDataParseError, ParsedGachaData, parse_gacha_data};
+    use crate::domain::{GachaEvent, GachaPrize};
+    use crate::sql_dump::Location;
+    use jiff::civil::date;
+
+    const DUMP: &str = "\
+CREATE TABLE `gacha_events` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `event_name` varchar(30) NOT NULL,
+  `event_start_time` datetime NOT NULL,
+  `event_end_time` datetime NOT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `gacha_events` VALUES (1,'正月','2024-01-01 00:00:00','2024-01-07 23:59:59');
+CREATE TABLE `gachadata` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `probability` double NOT NULL,
+  `itemstack` blob DEFAULT NULL,
+  `event_id` int(11) DEFAULT NULL,
+  PRIMARY KEY (`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `gachadata` VALUES (1,0.01,'rO0AB',NULL),(2,0.5,NULL,1);
+-- Dump completed
+";
+
+    #[test]
+    fn prizes_and_events_are_parsed() {
+        let ParsedGachaData {
+            gacha,
+            skipped_rows,
+        } = parse_gacha_data(DUMP.as_bytes()).unwrap();
+        assert_eq!(skipped_rows, []);
+
+        assert_eq!(
+            gacha.prizes,
+            [
+                GachaPrize {
+                    id: 1,
+                    probability: 0.01,
+                    itemstack: Some("rO0AB".into()),
+                    item: None,
+                    event_id: None,
+                },
+                GachaPrize {
+                    id: 2,
+                    probability: 0.5,
+                    itemstack: None,
+                    item: None,
+                    event_id: Some(1),
+                },
+            ]
+        );
+        assert_eq!(
+            gacha.events,
+            [GachaEvent {
+                id: 1,
+                name: "正月".to_owned(),
+                start_time: date(2024, 1, 1).at(0, 0, 0, 0),
+                end_time: date(2024, 1, 7).at(23, 59, 59, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn item_stacks_are_decoded_where_possible() {
+        let pickaxe = include_str!("../fixtures/item_stacks/enchanted_pickaxe.txt")
+            .trim_end()
+            .replace('\n', "\\n");
+        let dump = DUMP.replace("(2,0.5,NULL,1)", &format!("(2,0.5,'{pickaxe}',1)"));
+
+        let gacha = parse_gacha_data(dump.as_bytes()).unwrap().gacha;
+        assert_eq!(gacha.prizes[0].item, None, "読み取れない itemstack");
+        let item = gacha.prizes[1].item.as_ref().unwrap();
+        assert_eq!(item.material, "DIAMOND_PICKAXE");
+        assert_eq!(item.enchantments["DIG_SPEED"], 7);
+    }
+
+    #[test]
+    fn older_schema_without_events_is_accepted() {
+        let start = DUMP.find("CREATE TABLE `gachadata`").unwrap();
+        let dump = DUMP[start..]
+            .replace("  `event_id` int(11) DEFAULT NULL,\n", "")
+            .replace(",NULL)", ")")
+            .replace(",1)", ")");
+
+        let gacha = parse_gacha_data(dump.as_bytes()).unwrap().gacha;
+        assert_eq!(gacha.prizes.len(), 2);
+        assert!(gacha.prizes.iter().all(|prize| prize.event_id.is_none()));
+        assert!(gacha.events.is_empty());
+    }
+
+    #[test]
+    fn rows_with_invalid_values_are_skipped_with_their_row_and_column() {
+        let dump = DUMP.replace("(2,0.5,NULL,1)", "(2,NULL,NULL,1)");
+        let parsed = parse_gacha_data(dump.as_bytes()).unwrap();
+        assert_eq!(
+            parsed
+                .gacha
+                .prizes
+                .iter()
+                .map(|prize| prize.id)
+                .collect::<Vec<_>>(),
+            [1],
+            "他の行は読める"
+        );
+        assert_eq!(parsed.gacha.events.len(), 1);
+        let [error] = &parsed.skipped_rows[..] else {
+            panic!("unexpected skipped rows: {:?}", parsed.skipped_rows);
+        };
+        assert_eq!(
+            *error,
+            GachaDataParseError::InvalidValue {
+                table: "gachadata",
+                column: "probability",
+                location: Location {
+                    line: 16,
+                    column: 54,
+                },
+                reason: "must not be NULL".to_owned(),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "line 16, column 54: `gachadata`.`probability` must not be NULL"
+        );
+
+        let dump = DUMP.replace("'2024-01-07 23:59:59'", "'0000-00-00 00:00:00'");
+        let parsed = parse_gacha_data(dump.as_bytes()).unwrap();
+        assert_eq!(parsed.gacha.prizes.len(), 2);
+        assert!(parsed.gacha.events.is_empty());
+        assert!(
+            parsed.skipped_rows[0].to_string().starts_with(
+                "line 8, column 35: `gacha_events`.`event_end_time` must be a DATETIME"
+            )
+        );
+
+        let dump = DUMP.replace("`probability` double", "`chance` double");
+        assert_eq!(
+            parse_gacha_data(dump.as_bytes()),
+            Err(GachaDataParseError::MissingColumn {
+                table: "gachadata",
+                column: "probability",
+            })
+        );
+    }
+}
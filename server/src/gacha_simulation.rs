@@ -0,0 +1,160 @@
+use crate::domain::GachaPrize;
+
+/// 1 回のシミュレーションで引ける回数の上限
+pub const MAX_PULLS: u64 = 1_000_000;
+
+/// ガチャを引いた結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+    /// 景品ごとの当たった回数 (`simulate` に渡した景品と同じ順)
+    pub counts: Vec<u64>,
+    /// 景品ごとの、最初に当たったのが何回目か (1 始まり)。当たらなかった景品は `None`
+    pub first_hits: Vec<Option<u64>>,
+    /// どの景品にも当たらなかった回数
+    pub misses: u64,
+}
+
+/// `prizes` のガチャを `pulls` 回引く
+///
+/// `[0, 1)` の乱数が景品の確率を順に足した値を初めて下回った景品を当たりとし、
+/// 確率の合計を超えた場合ははずれとする。そのため確率の合計が 1 を超えると、後ろの景品ほど出にくくなる。
+/// 同じ `seed` からは、依存ライブラリのバージョンによらず同じ結果になる。
+pub fn simulate(prizes: &[&GachaPrize], pulls: u64, seed: u64) -> Simulation {
+    let thresholds = thresholds(prizes);
+
+    let mut simulation = Simulation {
+        counts: vec![0; prizes.len()],
+        first_hits: vec![None; prizes.len()],
+        misses: 0,
+    };
+    let mut rng = SplitMix64(seed);
+    for pull in 1..=pulls {
+        let random = rng.next_f64();
+        match thresholds.iter().position(|&threshold| random < threshold) {
+            Some(index) => {
+                simulation.counts[index] += 1;
+                simulation.first_hits[index].get_or_insert(pull);
+            }
+            None => simulation.misses += 1,
+        }
+    }
+    simulation
+}
+
+/// 景品ごとの、当たるまでに引く回数の期待値 (実際に当たる確率の逆数)
+///
+/// [`simulate`] と同じく、確率の合計が 1 を超えた分は当たらないものとする。
+/// 当たらない景品は `None`。
+pub fn expected_pulls(prizes: &[&GachaPrize]) -> Vec<Option<f64>> {
+    let mut previous = 0.0;
+    thresholds(prizes)
+        .into_iter()
+        .map(|threshold| {
+            let probability = threshold.min(1.0) - f64::min(previous, 1.0);
+            previous = threshold;
+            (probability > 0.0).then(|| 1.0 / probability)
+        })
+        .collect()
+}
+
+/// 景品の確率を順に足した値
+fn thresholds(prizes: &[&GachaPrize]) -> Vec<f64> {
+    let mut cumulative = 0.0;
+    prizes
+        .iter()
+        .map(|prize| {
+            cumulative += prize.probability.max(0.0);
+            cumulative
+        })
+        .collect()
+}
+
+/// SplitMix64 (<https://prng.di.unimi.it/splitmix64.c>)
+///
+/// 結果を seed で再現できるよう、アルゴリズムが変わりうる外部の乱数ライブラリは使わない。
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// 上位 53 ビットから作る `[0, 1)` の一様乱数
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SplitMix64, expected_pulls, simulate};
+    use crate::domain::GachaPrize;
+
+    fn prize(id: i32, probability: f64) -> GachaPrize {
+        GachaPrize {
+            id,
+            probability,
+            itemstack: None,
+            item: None,
+            event_id: None,
+        }
+    }
+
+    #[test]
+    fn splitmix64_matches_reference_implementation() {
+        // splitmix64.c に seed 1234567 を与えたときの最初の出力
+        let mut rng = SplitMix64(1234567);
+        assert_eq!(
+            [rng.next_u64(), rng.next_u64(), rng.next_u64()],
+            [
+                6457827717110365317,
+                3203168211198807973,
+                9817491932198370423
+            ]
+        );
+    }
+
+    #[test]
+    fn same_seed_gives_same_result_close_to_probabilities() {
+        let prizes = [prize(1, 0.01), prize(2, 0.2), prize(3, 0.5)];
+        let prizes: Vec<_> = prizes.iter().collect();
+
+        let simulation = simulate(&prizes, 100_000, 42);
+        assert_eq!(simulation, simulate(&prizes, 100_000, 42));
+        assert_ne!(simulation, simulate(&prizes, 100_000, 43));
+
+        assert_eq!(
+            simulation.counts.iter().sum::<u64>() + simulation.misses,
+            100_000
+        );
+        for (count, expected) in simulation.counts.iter().zip([1_000, 20_000, 50_000]) {
+            let error = (*count as f64 - expected as f64).abs() / expected as f64;
+            assert!(error < 0.1, "{count} は {expected} から離れすぎている");
+        }
+        let misses_error = (simulation.misses as f64 - 29_000.0).abs() / 29_000.0;
+        assert!(misses_error < 0.1);
+        assert!(simulation.first_hits.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn prizes_beyond_total_probability_of_one_are_never_drawn() {
+        let prizes = [prize(1, 0.7), prize(2, 0.6), prize(3, 0.0)];
+        let prizes: Vec<_> = prizes.iter().collect();
+
+        let simulation = simulate(&prizes, 10_000, 0);
+        assert_eq!(simulation.misses, 0);
+        assert_eq!(simulation.counts[2], 0);
+        assert_eq!(simulation.first_hits[2], None);
+        // 2 つ目の景品は 1 - 0.7 = 0.3 の確率でしか出ない
+        assert!((2_500..3_500).contains(&simulation.counts[1]));
+
+        let expected = expected_pulls(&prizes);
+        assert!((expected[0].unwrap() - 1.0 / 0.7).abs() < 1e-9);
+        assert!((expected[1].unwrap() - 1.0 / 0.3).abs() < 1e-9);
+        assert_eq!(expected[2], None);
+    }
+}
@@ -0,0 +1,334 @@
+use crate::config::Gacha;
+use crate::domain::GachaDataRepository;
+use crate::gacha_api::{SingleGacha, no_such_event};
+use crate::give_command::{MinecraftVersion, UnknownLegacyItem, give_command, item_snbt};
+use crate::loot_table;
+use crate::presentation::{internal_server_error, published_gacha, versioned_json};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{ErrorResponse, IntoResponse, Response, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GiveQuery {
+    /// 省略時は `GACHA_MINECRAFT_VERSION`
+    minecraft_version: Option<MinecraftVersion>,
+}
+
+#[derive(Serialize)]
+pub struct GiveResponse {
+    version: String,
+    minecraft_version: MinecraftVersion,
+    prize_id: i32,
+    /// チャットやコマンドブロックで実行する `/give` コマンド
+    command: String,
+    snbt: String,
+}
+
+/// 景品のアイテムを再現する `/give` コマンドと SNBT を返す
+#[tracing::instrument(skip(repository, settings, request_headers))]
+pub async fn get_prize_give_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    State(settings): State<Arc<Gacha>>,
+    Path(id): Path<i32>,
+    Query(query): Query<GiveQuery>,
+    request_headers: HeaderMap,
+) -> Result<Response> {
+    let (version, gacha) = published_gacha(&repository).await?;
+    let prize = gacha
+        .prizes
+        .iter()
+        .find(|prize| prize.id == id)
+        .ok_or_else(|| {
+            ErrorResponse::from(
+                (StatusCode::NOT_FOUND, "No prize with the given id.").into_response(),
+            )
+        })?;
+    let item = prize.item.as_ref().ok_or_else(|| {
+        ErrorResponse::from(
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "The item of the prize could not be decoded.",
+            )
+                .into_response(),
+        )
+    })?;
+    let minecraft_version = query
+        .minecraft_version
+        .unwrap_or(settings.minecraft_version);
+    let unexportable = |error: UnknownLegacyItem| {
+        ErrorResponse::from(
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("The item of the prize cannot be given: {error}"),
+            )
+                .into_response(),
+        )
+    };
+
+    Ok(versioned_json(
+        &version,
+        &request_headers,
+        GiveResponse {
+            version: version.0.clone(),
+            minecraft_version,
+            prize_id: prize.id,
+            command: format!(
+                "/{}",
+                give_command(item, minecraft_version).map_err(unexportable)?
+            ),
+            snbt: item_snbt(item, minecraft_version).map_err(unexportable)?,
+        },
+    ))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GiveFunctionQuery {
+    /// イベント名。省略するか空文字なら通常のガチャ
+    event: Option<String>,
+    /// 省略時は `GACHA_MINECRAFT_VERSION`
+    minecraft_version: Option<MinecraftVersion>,
+}
+
+/// 景品をすべて `give` する function (`.mcfunction`) を返す
+///
+/// アイテムを読み取れなかった景品や、アイテム ID が分からない景品はコメントとして残す。
+#[tracing::instrument(skip(repository, settings))]
+pub async fn get_give_function_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    State(settings): State<Arc<Gacha>>,
+    Query(query): Query<GiveFunctionQuery>,
+) -> Result<Response> {
+    let (version, gacha) = published_gacha(&repository).await?;
+    let single =
+        SingleGacha::from_query(&gacha, query.event.as_deref()).ok_or_else(no_such_event)?;
+    let minecraft_version = query
+        .minecraft_version
+        .unwrap_or(settings.minecraft_version);
+
+    let mut function = format!("# gachadata {version} の景品 (Minecraft {minecraft_version})\n");
+    for prize in single.prizes(&gacha) {
+        let heading = format!("# 景品 #{} (確率 {})", prize.id, prize.probability);
+        match prize
+            .item
+            .as_ref()
+            .map(|item| give_command(item, minecraft_version))
+        {
+            Some(Ok(command)) => function += &format!("{heading}\n{command}\n"),
+            Some(Err(error)) => function += &format!("{heading}: {error} のため省略\n"),
+            None => function += &format!("{heading}: アイテムを読み取れないため省略\n"),
+        }
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.mcfunction\"", single.file_stem()),
+            ),
+            (
+                header::HeaderName::from_static("x-gachadata-version"),
+                version.to_string(),
+            ),
+        ],
+        function,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DatapackQuery {
+    /// イベント名。省略するか空文字なら通常のガチャ
+    event: Option<String>,
+    /// 省略時は `GACHA_MINECRAFT_VERSION`
+    minecraft_version: Option<MinecraftVersion>,
+}
+
+/// 景品を 1 回引くルートテーブルを入れたデータパック (zip) を返す
+///
+/// `GET /` と同じくキャッシュ済みの dump から作るため、同時に取得した SQL の dump と内容が一致する。
+#[tracing::instrument(skip(repository, settings))]
+pub async fn get_datapack_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    State(settings): State<Arc<Gacha>>,
+    Query(query): Query<DatapackQuery>,
+) -> Result<Response> {
+    let (version, gacha) = published_gacha(&repository).await?;
+    let single =
+        SingleGacha::from_query(&gacha, query.event.as_deref()).ok_or_else(no_such_event)?;
+    let minecraft_version = query
+        .minecraft_version
+        .unwrap_or(settings.minecraft_version);
+
+    let prizes = single.prizes(&gacha);
+    let name = single.file_stem();
+    let description = format!(
+        "gachadata {}: /loot give @s loot {}:{name}",
+        version.0.get(..12).unwrap_or(&version.0),
+        loot_table::NAMESPACE
+    );
+    let datapack = loot_table::datapack(
+        &name,
+        &loot_table::loot_table(&prizes, minecraft_version),
+        &description,
+        minecraft_version,
+    )
+    .map_err(|err| internal_server_error(err.into()))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{name}.zip\""),
+            ),
+            (
+                header::HeaderName::from_static("x-gachadata-version"),
+                version.to_string(),
+            ),
+        ],
+        datapack,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DatapackQuery, GiveFunctionQuery, GiveQuery, get_datapack_handler,
+        get_give_function_handler, get_prize_give_handler,
+    };
+    use crate::presentation::get_gachadata_handler;
+    use crate::presentation::test_support::{gacha_repository, gacha_settings, json_body};
+    use axum::extract::{Path, Query, State};
+    use axum::http::{HeaderMap, StatusCode, header};
+    use axum::response::IntoResponse;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn give_commands_are_rendered_for_configured_or_requested_version() {
+        let repository = gacha_repository();
+        let give = |id, minecraft_version: Option<&str>| {
+            let repository = Arc::clone(&repository);
+            let minecraft_version = minecraft_version.map(|version| version.parse().unwrap());
+            async move {
+                get_prize_give_handler(
+                    State(repository),
+                    State(gacha_settings()),
+                    Path(id),
+                    Query(GiveQuery { minecraft_version }),
+                    HeaderMap::new(),
+                )
+                .await
+                .into_response()
+            }
+        };
+
+        let json = json_body(give(2, None).await).await;
+        assert_eq!(json["minecraft_version"], "1.18.2");
+        assert_eq!(
+            json["command"],
+            r#"/give @p minecraft:diamond{display:{Name:'"§bすごいダイヤ"',Lore:['"§7景品 #2"']},Enchantments:[{id:"minecraft:unbreaking",lvl:3s}]} 1"#
+        );
+        let json = json_body(give(2, Some("1.21")).await).await;
+        assert!(
+            json["command"]
+                .as_str()
+                .unwrap()
+                .starts_with("/give @p minecraft:diamond[minecraft:custom_name=")
+        );
+        assert_eq!(give(99, None).await.status(), StatusCode::NOT_FOUND);
+
+        let response = get_give_function_handler(
+            State(repository),
+            State(gacha_settings()),
+            Query(GiveFunctionQuery {
+                event: Some("正月".to_owned()),
+                minecraft_version: None,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"gacha-event-1.mcfunction\""
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let function = String::from_utf8(body.to_vec()).unwrap();
+        let commands: Vec<_> = function
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        assert_eq!(commands.len(), 2, "イベント「正月」の景品は 2 つ");
+        assert!(
+            commands
+                .iter()
+                .all(|command| command.starts_with("give @p minecraft:diamond{"))
+        );
+    }
+
+    #[tokio::test]
+    async fn datapack_is_built_from_the_same_snapshot_as_the_sql_dump() {
+        use std::io::Read;
+
+        let repository = gacha_repository();
+        let sql = get_gachadata_handler(State(Arc::clone(&repository)), HeaderMap::new())
+            .await
+            .unwrap()
+            .into_response();
+        let response = get_datapack_handler(
+            State(repository),
+            State(gacha_settings()),
+            Query(DatapackQuery {
+                event: Some("正月".to_owned()),
+                minecraft_version: None,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.headers()["x-gachadata-version"],
+            sql.headers()["x-gachadata-version"]
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"gacha-event-1.zip\""
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body)).unwrap();
+        let mut loot_table = String::new();
+        archive
+            .by_name("data/gachadata/loot_tables/gacha-event-1.json")
+            .unwrap()
+            .read_to_string(&mut loot_table)
+            .unwrap();
+        let loot_table: serde_json::Value = serde_json::from_str(&loot_table).unwrap();
+        let weights: Vec<_> = loot_table["pools"][0]["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["weight"].as_i64().unwrap())
+            .collect();
+        assert_eq!(weights, [10_000, 200_000, 790_000]);
+
+        let regular = get_datapack_handler(
+            State(gacha_repository()),
+            State(gacha_settings()),
+            Query(DatapackQuery::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            regular.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"gacha-regular.zip\"",
+            "イベントを省略すると、イベントの景品を混ぜずに通常のガチャにする"
+        );
+    }
+}
@@ -0,0 +1,663 @@
+use crate::domain::ItemStack;
+use crate::item_stack::text_component;
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+
+/// `give` コマンドと SNBT を出力する Minecraft のバージョン
+///
+/// アイテムの NBT の形が変わったバージョンごとに出力を切り替える。
+/// 説明文が JSON のテキストコンポーネントになった 1.14 より前は扱わない。
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct MinecraftVersion {
+    major: u16,
+    minor: u16,
+    patch: u16,
+}
+
+impl MinecraftVersion {
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        MinecraftVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    const OLDEST_SUPPORTED: Self = Self::new(1, 14, 0);
+    /// アイテムの NBT (`tag`) がアイテムコンポーネントに置き換わったバージョン
+    const ITEM_COMPONENTS: Self = Self::new(1, 20, 5);
+    /// `custom_model_data` が整数から `{floats:[...]}` になったバージョン
+    const CUSTOM_MODEL_DATA_FLOATS: Self = Self::new(1, 21, 4);
+    /// テキストコンポーネントが JSON の文字列から SNBT になり、
+    /// `enchantments` の `levels` がなくなったバージョン
+    const SNBT_TEXT_COMPONENTS: Self = Self::new(1, 21, 5);
+}
+
+impl Display for MinecraftVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+pub enum MinecraftVersionError {
+    #[error("invalid Minecraft version: {0:?}")]
+    Invalid(String),
+    #[error("Minecraft {0} is not supported (1.14 or later is required)")]
+    Unsupported(MinecraftVersion),
+}
+
+impl FromStr for MinecraftVersion {
+    type Err = MinecraftVersionError;
+
+    fn from_str(version: &str) -> Result<Self, Self::Err> {
+        let invalid = || MinecraftVersionError::Invalid(version.to_owned());
+        let numbers = version
+            .split('.')
+            .map(|number| number.parse::<u16>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let version = match numbers[..] {
+            [major, minor] => MinecraftVersion::new(major, minor, 0),
+            [major, minor, patch] => MinecraftVersion::new(major, minor, patch),
+            _ => return Err(invalid()),
+        };
+        if version < MinecraftVersion::OLDEST_SUPPORTED {
+            return Err(MinecraftVersionError::Unsupported(version));
+        }
+        Ok(version)
+    }
+}
+
+impl TryFrom<String> for MinecraftVersion {
+    type Error = MinecraftVersionError;
+
+    fn try_from(version: String) -> Result<Self, Self::Error> {
+        version.parse()
+    }
+}
+
+impl From<MinecraftVersion> for String {
+    fn from(version: MinecraftVersion) -> Self {
+        version.to_string()
+    }
+}
+
+/// 1.13 以降のアイテム ID が分からない、1.13 より前のアイテム
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+#[error("no item ID is known for legacy item {material} with damage {damage}")]
+pub struct UnknownLegacyItem {
+    pub material: String,
+    pub damage: i16,
+}
+
+/// アイテムを `version` の `give` コマンド (先頭の `/` なし) にする
+///
+/// 対象は `@p` (チャットでは自分、コマンドブロックや function では最も近いプレイヤー)。
+pub fn give_command(
+    item: &ItemStack,
+    version: MinecraftVersion,
+) -> Result<String, UnknownLegacyItem> {
+    let mut command = format!("give @p {}", item_id(item)?);
+    let data = item_data(item, version);
+    if !data.is_empty() {
+        if version >= MinecraftVersion::ITEM_COMPONENTS {
+            command.push('[');
+            for (index, (component, value)) in data.iter().enumerate() {
+                if index > 0 {
+                    command.push(',');
+                }
+                write!(command, "{component}={value}").unwrap();
+            }
+            command.push(']');
+        } else {
+            write!(command, "{}", Snbt::Compound(data)).unwrap();
+        }
+    }
+    write!(command, " {}", item.amount).unwrap();
+    Ok(command)
+}
+
+/// アイテムを `version` の SNBT (`/data` やストラクチャーで使うアイテムの NBT) にする
+pub fn item_snbt(item: &ItemStack, version: MinecraftVersion) -> Result<String, UnknownLegacyItem> {
+    let id = ("id".to_owned(), Snbt::String(item_id(item)?));
+    let data = item_data(item, version);
+    let mut snbt = if version >= MinecraftVersion::ITEM_COMPONENTS {
+        vec![id, ("count".to_owned(), Snbt::Int(item.amount))]
+    } else {
+        let count = i8::try_from(item.amount).unwrap_or(i8::MAX);
+        vec![id, ("Count".to_owned(), Snbt::Byte(count))]
+    };
+    if !data.is_empty() {
+        let key = if version >= MinecraftVersion::ITEM_COMPONENTS {
+            "components"
+        } else {
+            "tag"
+        };
+        snbt.push((key.to_owned(), Snbt::Compound(data)));
+    }
+    Ok(Snbt::Compound(snbt).to_string())
+}
+
+/// アイテムを `version` の `tag` (1.20.5 より前のアイテムの NBT) の SNBT にする
+///
+/// 1.20.5 以降か、`tag` に入れるものがなければ `None`。
+pub fn item_tag(item: &ItemStack, version: MinecraftVersion) -> Option<String> {
+    let data = item_data(item, version);
+    (version < MinecraftVersion::ITEM_COMPONENTS && !data.is_empty())
+        .then(|| Snbt::Compound(data).to_string())
+}
+
+/// アイテムを `version` のアイテムコンポーネントを JSON にしたもの (ルートテーブルなどで使う) にする
+///
+/// 1.20.5 より前か、コンポーネントがなければ `None`。
+pub fn item_components(
+    item: &ItemStack,
+    version: MinecraftVersion,
+) -> Option<serde_json::Map<String, serde_json::Value>> {
+    let data = item_data(item, version);
+    (version >= MinecraftVersion::ITEM_COMPONENTS && !data.is_empty()).then(|| {
+        data.into_iter()
+            .map(|(component, value)| (component, value.to_json()))
+            .collect()
+    })
+}
+
+/// 名前空間付きのアイテム ID
+///
+/// 1.13 以降の Bukkit の Material 名は、名前空間を除いたアイテム ID を大文字にしたもの。
+/// 1.13 より前の data value (`damage`) で種類が決まるアイテムは、対応する ID にする。
+/// 対応が分からない場合は、別のアイテムにならないよう [`UnknownLegacyItem`] を返す。
+/// ツールや防具の `damage` は耐久値の減少量なので、ID には影響せず出力もしない。
+pub fn item_id(item: &ItemStack) -> Result<String, UnknownLegacyItem> {
+    let unknown = || UnknownLegacyItem {
+        material: item.material.clone(),
+        damage: item.damage,
+    };
+    let name = match legacy_variants(&item.material) {
+        Some(variants) => usize::try_from(item.damage)
+            .ok()
+            .and_then(|damage| variants.into_iter().nth(damage))
+            .ok_or_else(unknown)?,
+        None if item.damage == 0 || has_durability(&item.material) => {
+            item.material.to_ascii_lowercase()
+        }
+        None => return Err(unknown()),
+    };
+    Ok(format!("minecraft:{name}"))
+}
+
+/// 1.13 より前に data value で種類を分けていたアイテムの、data value 順の 1.13 以降の ID
+fn legacy_variants(material: &str) -> Option<Vec<String>> {
+    const COLORS: [&str; 16] = [
+        "white",
+        "orange",
+        "magenta",
+        "light_blue",
+        "yellow",
+        "lime",
+        "pink",
+        "gray",
+        "light_gray",
+        "cyan",
+        "purple",
+        "blue",
+        "brown",
+        "green",
+        "red",
+        "black",
+    ];
+    const DYES: [&str; 16] = [
+        "ink_sac",
+        "red_dye",
+        "green_dye",
+        "cocoa_beans",
+        "lapis_lazuli",
+        "purple_dye",
+        "cyan_dye",
+        "light_gray_dye",
+        "gray_dye",
+        "pink_dye",
+        "lime_dye",
+        "yellow_dye",
+        "light_blue_dye",
+        "magenta_dye",
+        "orange_dye",
+        "bone_meal",
+    ];
+
+    let names = |names: &[&str]| names.iter().map(|name| (*name).to_owned()).collect();
+    let colored = |suffix: &str| {
+        COLORS
+            .iter()
+            .map(|color| format!("{color}_{suffix}"))
+            .collect()
+    };
+    Some(match material {
+        "GOLDEN_APPLE" => names(&["golden_apple", "enchanted_golden_apple"]),
+        "SKULL_ITEM" => names(&[
+            "skeleton_skull",
+            "wither_skeleton_skull",
+            "zombie_head",
+            "player_head",
+            "creeper_head",
+            "dragon_head",
+        ]),
+        "COAL" => names(&["coal", "charcoal"]),
+        "INK_SACK" => names(&DYES),
+        "WOOL" => colored("wool"),
+        "CARPET" => colored("carpet"),
+        "STAINED_GLASS" => colored("stained_glass"),
+        "STAINED_GLASS_PANE" => colored("stained_glass_pane"),
+        "STAINED_CLAY" => colored("terracotta"),
+        _ => return None,
+    })
+}
+
+/// `damage` が耐久値の減少量を表すアイテム (ツール・武器・防具など) かどうか
+fn has_durability(material: &str) -> bool {
+    const SUFFIXES: &[&str] = &[
+        "_PICKAXE",
+        "_AXE",
+        "_SPADE",
+        "_SHOVEL",
+        "_HOE",
+        "_SWORD",
+        "_HELMET",
+        "_CHESTPLATE",
+        "_LEGGINGS",
+        "_BOOTS",
+    ];
+    const ITEMS: &[&str] = &[
+        "BOW",
+        "CROSSBOW",
+        "TRIDENT",
+        "FISHING_ROD",
+        "SHEARS",
+        "FLINT_AND_STEEL",
+        "CARROT_STICK",
+        "CARROT_ON_A_STICK",
+        "SHIELD",
+        "ELYTRA",
+    ];
+    SUFFIXES.iter().any(|suffix| material.ends_with(suffix)) || ITEMS.contains(&material)
+}
+
+/// 1.20.5 より前は `tag` の中身、それ以降はアイテムコンポーネント
+fn item_data(item: &ItemStack, version: MinecraftVersion) -> Vec<(String, Snbt)> {
+    let mut data = Vec::new();
+    if version < MinecraftVersion::ITEM_COMPONENTS {
+        let mut display = Vec::new();
+        if let Some(name) = &item.display_name {
+            display.push(("Name".to_owned(), text(name, version)));
+        }
+        if !item.lore.is_empty() {
+            let lore = item.lore.iter().map(|line| text(line, version)).collect();
+            display.push(("Lore".to_owned(), Snbt::List(lore)));
+        }
+        if !display.is_empty() {
+            data.push(("display".to_owned(), Snbt::Compound(display)));
+        }
+        if !item.enchantments.is_empty() {
+            let enchantments = item
+                .enchantments
+                .iter()
+                .map(|(enchantment, &level)| {
+                    Snbt::Compound(vec![
+                        (
+                            "id".to_owned(),
+                            Snbt::String(enchantment_id(enchantment, version)),
+                        ),
+                        (
+                            "lvl".to_owned(),
+                            Snbt::Short(i16::try_from(level).unwrap_or(i16::MAX)),
+                        ),
+                    ])
+                })
+                .collect();
+            data.push(("Enchantments".to_owned(), Snbt::List(enchantments)));
+        }
+        if let Some(custom_model_data) = item.custom_model_data {
+            data.push(("CustomModelData".to_owned(), Snbt::Int(custom_model_data)));
+        }
+        return data;
+    }
+
+    if let Some(name) = &item.display_name {
+        data.push(("minecraft:custom_name".to_owned(), text(name, version)));
+    }
+    if !item.lore.is_empty() {
+        let lore = item.lore.iter().map(|line| text(line, version)).collect();
+        data.push(("minecraft:lore".to_owned(), Snbt::List(lore)));
+    }
+    if !item.enchantments.is_empty() {
+        let levels = Snbt::Compound(
+            item.enchantments
+                .iter()
+                .map(|(enchantment, &level)| {
+                    (enchantment_id(enchantment, version), Snbt::Int(level))
+                })
+                .collect(),
+        );
+        let enchantments = if version >= MinecraftVersion::SNBT_TEXT_COMPONENTS {
+            levels
+        } else {
+            Snbt::Compound(vec![("levels".to_owned(), levels)])
+        };
+        data.push(("minecraft:enchantments".to_owned(), enchantments));
+    }
+    if let Some(custom_model_data) = item.custom_model_data {
+        let custom_model_data = if version >= MinecraftVersion::CUSTOM_MODEL_DATA_FLOATS {
+            Snbt::Compound(vec![(
+                "floats".to_owned(),
+                Snbt::List(vec![Snbt::Float(custom_model_data as f32)]),
+            )])
+        } else {
+            Snbt::Int(custom_model_data)
+        };
+        data.push(("minecraft:custom_model_data".to_owned(), custom_model_data));
+    }
+    data
+}
+
+/// 表示名や説明文。1.21.5 より前は JSON のテキストコンポーネントを文字列にしたもの
+fn text(text: &str, version: MinecraftVersion) -> Snbt {
+    let component = text_component(text);
+    if version >= MinecraftVersion::SNBT_TEXT_COMPONENTS {
+        Snbt::from_json(component)
+    } else {
+        Snbt::String(component.to_string())
+    }
+}
+
+/// Bukkit のエンチャント名 (`DIG_SPEED` など) に対応する、名前空間付きの ID
+fn enchantment_id(enchantment: &str, version: MinecraftVersion) -> String {
+    const LEGACY_NAMES: &[(&str, &str)] = &[
+        ("PROTECTION_ENVIRONMENTAL", "protection"),
+        ("PROTECTION_FIRE", "fire_protection"),
+        ("PROTECTION_FALL", "feather_falling"),
+        ("PROTECTION_EXPLOSIONS", "blast_protection"),
+        ("PROTECTION_PROJECTILE", "projectile_protection"),
+        ("OXYGEN", "respiration"),
+        ("WATER_WORKER", "aqua_affinity"),
+        ("DAMAGE_ALL", "sharpness"),
+        ("DAMAGE_UNDEAD", "smite"),
+        ("DAMAGE_ARTHROPODS", "bane_of_arthropods"),
+        ("LOOT_BONUS_MOBS", "looting"),
+        ("DIG_SPEED", "efficiency"),
+        ("DURABILITY", "unbreaking"),
+        ("LOOT_BONUS_BLOCKS", "fortune"),
+        ("ARROW_DAMAGE", "power"),
+        ("ARROW_KNOCKBACK", "punch"),
+        ("ARROW_FIRE", "flame"),
+        ("ARROW_INFINITE", "infinity"),
+        ("LUCK", "luck_of_the_sea"),
+    ];
+
+    if enchantment.contains(':') {
+        return enchantment.to_owned();
+    }
+    let name = LEGACY_NAMES
+        .iter()
+        .find(|(legacy_name, _)| *legacy_name == enchantment)
+        .map(|(_, name)| (*name).to_owned())
+        .unwrap_or_else(|| enchantment.to_ascii_lowercase());
+    // 1.20.5 で sweeping から sweeping_edge に改名された
+    let name = match name.as_str() {
+        "sweeping" | "sweeping_edge" if version >= MinecraftVersion::ITEM_COMPONENTS => {
+            "sweeping_edge".to_owned()
+        }
+        "sweeping" | "sweeping_edge" => "sweeping".to_owned(),
+        _ => name,
+    };
+    format!("minecraft:{name}")
+}
+
+/// 出力する SNBT の値
+enum Snbt {
+    String(String),
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    List(Vec<Snbt>),
+    Compound(Vec<(String, Snbt)>),
+}
+
+impl Snbt {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Snbt::String(string) => string.clone().into(),
+            Snbt::Byte(byte) => (*byte).into(),
+            Snbt::Short(short) => (*short).into(),
+            Snbt::Int(int) => (*int).into(),
+            Snbt::Long(long) => (*long).into(),
+            Snbt::Float(float) => (*float).into(),
+            Snbt::Double(double) => (*double).into(),
+            Snbt::Boolean(boolean) => (*boolean).into(),
+            Snbt::List(values) => values.iter().map(Snbt::to_json).collect(),
+            Snbt::Compound(entries) => entries
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_json()))
+                .collect(),
+        }
+    }
+
+    fn from_json(json: serde_json::Value) -> Snbt {
+        match json {
+            serde_json::Value::Null => Snbt::String(String::new()),
+            serde_json::Value::Bool(boolean) => Snbt::Boolean(boolean),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(integer) => i32::try_from(integer).map_or(Snbt::Long(integer), Snbt::Int),
+                None => Snbt::Double(number.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(string) => Snbt::String(string),
+            serde_json::Value::Array(values) => {
+                Snbt::List(values.into_iter().map(Snbt::from_json).collect())
+            }
+            serde_json::Value::Object(entries) => Snbt::Compound(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, Snbt::from_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Display for Snbt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Snbt::String(string) => write_quoted(f, string),
+            Snbt::Byte(byte) => write!(f, "{byte}b"),
+            Snbt::Short(short) => write!(f, "{short}s"),
+            Snbt::Int(int) => write!(f, "{int}"),
+            Snbt::Long(long) => write!(f, "{long}L"),
+            Snbt::Float(float) => write!(f, "{float}f"),
+            Snbt::Double(double) => write!(f, "{double}d"),
+            Snbt::Boolean(boolean) => write!(f, "{boolean}"),
+            Snbt::List(values) => {
+                f.write_char('[')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Snbt::Compound(entries) => {
+                f.write_char('{')?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    let is_plain_key = !key.is_empty()
+                        && key.chars().all(|c| {
+                            c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+                        });
+                    if is_plain_key {
+                        f.write_str(key)?;
+                    } else {
+                        write_quoted(f, key)?;
+                    }
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// Minecraft (`StringTag.quoteAndEscape`) と同じく、先に現れた引用符と異なる方で囲む
+fn write_quoted(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    let quote = match string.chars().find(|&c| c == '"' || c == '\'') {
+        Some('"') => '\'',
+        _ => '"',
+    };
+    f.write_char(quote)?;
+    for c in string.chars() {
+        if c == '\\' || c == quote {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char(quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MinecraftVersion, MinecraftVersionError, UnknownLegacyItem, give_command, item_id,
+        item_snbt,
+    };
+    use crate::domain::ItemStack;
+    use std::collections::BTreeMap;
+
+    fn pickaxe() -> ItemStack {
+        ItemStack {
+            material: "DIAMOND_PICKAXE".to_owned(),
+            amount: 1,
+            damage: 0,
+            display_name: Some(r#"{"text":"Tom's pick","color":"gold"}"#.to_owned()),
+            lore: vec!["§7Rank: 1".to_owned()],
+            enchantments: BTreeMap::from([
+                ("DIG_SPEED".to_owned(), 5),
+                ("DURABILITY".to_owned(), 3),
+            ]),
+            custom_model_data: Some(12),
+        }
+    }
+
+    fn version(version: &str) -> MinecraftVersion {
+        version.parse().unwrap()
+    }
+
+    #[test]
+    fn items_are_rendered_as_nbt_tag_before_item_components() {
+        let version = version("1.18.2");
+        let tag = r#"{display:{Name:'{"color":"gold","text":"Tom\'s pick"}',Lore:['"§7Rank: 1"']},Enchantments:[{id:"minecraft:efficiency",lvl:5s},{id:"minecraft:unbreaking",lvl:3s}],CustomModelData:12}"#;
+        assert_eq!(
+            give_command(&pickaxe(), version).unwrap(),
+            format!("give @p minecraft:diamond_pickaxe{tag} 1")
+        );
+        assert_eq!(
+            item_snbt(&pickaxe(), version).unwrap(),
+            format!(r#"{{id:"minecraft:diamond_pickaxe",Count:1b,tag:{tag}}}"#)
+        );
+
+        let stone = ItemStack {
+            material: "STONE".to_owned(),
+            amount: 64,
+            damage: 0,
+            display_name: None,
+            lore: Vec::new(),
+            enchantments: BTreeMap::new(),
+            custom_model_data: None,
+        };
+        assert_eq!(
+            give_command(&stone, version).unwrap(),
+            "give @p minecraft:stone 64"
+        );
+        assert_eq!(
+            item_snbt(&stone, version).unwrap(),
+            r#"{id:"minecraft:stone",Count:64b}"#
+        );
+    }
+
+    #[test]
+    fn items_are_rendered_as_item_components_from_1_20_5() {
+        assert_eq!(
+            give_command(&pickaxe(), version("1.20.5")).unwrap(),
+            r#"give @p minecraft:diamond_pickaxe[minecraft:custom_name='{"color":"gold","text":"Tom\'s pick"}',minecraft:lore=['"§7Rank: 1"'],minecraft:enchantments={levels:{"minecraft:efficiency":5,"minecraft:unbreaking":3}},minecraft:custom_model_data=12] 1"#
+        );
+        assert_eq!(
+            item_snbt(&pickaxe(), version("1.21.5")).unwrap(),
+            r#"{id:"minecraft:diamond_pickaxe",count:1,components:{"minecraft:custom_name":{color:"gold",text:"Tom's pick"},"minecraft:lore":["§7Rank: 1"],"minecraft:enchantments":{"minecraft:efficiency":5,"minecraft:unbreaking":3},"minecraft:custom_model_data":{floats:[12f]}}}"#
+        );
+    }
+
+    #[test]
+    fn legacy_data_values_select_the_modern_item() {
+        let legacy = |material: &str, damage| ItemStack {
+            material: material.to_owned(),
+            damage,
+            ..pickaxe()
+        };
+        assert_eq!(
+            item_id(&legacy("GOLDEN_APPLE", 0)).unwrap(),
+            "minecraft:golden_apple"
+        );
+        assert_eq!(
+            give_command(&legacy("GOLDEN_APPLE", 1), version("1.20.5"))
+                .unwrap()
+                .split('[')
+                .next(),
+            Some("give @p minecraft:enchanted_golden_apple")
+        );
+        assert_eq!(
+            item_id(&legacy("SKULL_ITEM", 3)).unwrap(),
+            "minecraft:player_head"
+        );
+        assert_eq!(item_id(&legacy("WOOL", 14)).unwrap(), "minecraft:red_wool");
+        assert_eq!(
+            item_id(&legacy("DIAMOND_PICKAXE", 120)).unwrap(),
+            "minecraft:diamond_pickaxe",
+            "ツールの damage は耐久値の減少量"
+        );
+        assert_eq!(
+            item_snbt(&legacy("LOG", 2), version("1.18.2")),
+            Err(UnknownLegacyItem {
+                material: "LOG".to_owned(),
+                damage: 2,
+            }),
+            "対応が分からないものは別のアイテムとして出力しない"
+        );
+        assert!(item_id(&legacy("SKULL_ITEM", 6)).is_err());
+    }
+
+    #[test]
+    fn versions_are_parsed_and_old_versions_are_rejected() {
+        assert_eq!(version("1.21").to_string(), "1.21");
+        assert!(version("1.20.4") < version("1.20.5"));
+        assert_eq!(
+            "1.12.2".parse::<MinecraftVersion>(),
+            Err(MinecraftVersionError::Unsupported(MinecraftVersion::new(
+                1, 12, 2
+            )))
+        );
+        assert_eq!(
+            "latest".parse::<MinecraftVersion>(),
+            Err(MinecraftVersionError::Invalid("latest".to_owned()))
+        );
+    }
+}
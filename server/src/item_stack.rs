@@ -0,0 +1,279 @@
+use crate::domain::ItemStack;
+use crate::java_serialization::{JavaStreamError, JavaValue, read_objects};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+pub enum ItemStackDecodeError {
+    #[error("itemstack is not valid Base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("failed to read serialized itemstack: {0}")]
+    Stream(#[from] JavaStreamError),
+    #[error("serialized inventory has no item")]
+    Empty,
+    #[error("serialized ItemStack has no valid `{0}`")]
+    InvalidKey(&'static str),
+}
+
+/// `gachadata.itemstack` を読み取る
+///
+/// SeichiAssist は `BukkitObjectOutputStream` で 9 スロットのインベントリ
+/// (スロット数の `int` と、スロットごとの ItemStack) を書き出し、Base64 (76 文字ごとに改行)
+/// にして保存している。景品のアイテムは先頭のスロットに入っている。
+/// Base64 にしていない直列化ストリームもそのまま読む。
+pub fn decode_item_stack(itemstack: &[u8]) -> Result<ItemStack, ItemStackDecodeError> {
+    let stream = if itemstack.starts_with(&[0xac, 0xed]) {
+        Cow::Borrowed(itemstack)
+    } else {
+        let base64: Vec<u8> = itemstack
+            .iter()
+            .copied()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .collect();
+        Cow::Owned(BASE64_STANDARD.decode(base64)?)
+    };
+
+    let objects = read_objects(&stream)?;
+    let item = objects
+        .iter()
+        .find(|object| **object != JavaValue::Null)
+        .ok_or(ItemStackDecodeError::Empty)?;
+    from_serialized(item)
+}
+
+/// `ItemStack.serialize()` の Map (`type`、`amount`、`meta` など) から読み取る
+fn from_serialized(item: &JavaValue) -> Result<ItemStack, ItemStackDecodeError> {
+    let material = item
+        .get("type")
+        .and_then(JavaValue::as_str)
+        .ok_or(ItemStackDecodeError::InvalidKey("type"))?
+        .to_owned();
+    let amount = match item.get("amount") {
+        // 1 個なら書き出されない
+        None => 1,
+        Some(amount) => integer(amount, "amount")?,
+    };
+    let damage = match item.get("damage") {
+        // 0 なら書き出されない
+        None => 0,
+        Some(damage) => i16::try_from(integer(damage, "damage")?)
+            .map_err(|_| ItemStackDecodeError::InvalidKey("damage"))?,
+    };
+
+    let mut item_stack = ItemStack {
+        material,
+        amount,
+        damage,
+        display_name: None,
+        lore: Vec::new(),
+        enchantments: BTreeMap::new(),
+        custom_model_data: None,
+    };
+    let Some(meta) = item.get("meta") else {
+        return Ok(item_stack);
+    };
+
+    if let Some(display_name) = meta.get("display-name") {
+        let display_name = display_name
+            .as_str()
+            .ok_or(ItemStackDecodeError::InvalidKey("display-name"))?;
+        item_stack.display_name = Some(display_name.to_owned());
+    }
+    if let Some(lore) = meta.get("lore") {
+        let JavaValue::List(lines) = lore else {
+            return Err(ItemStackDecodeError::InvalidKey("lore"));
+        };
+        item_stack.lore = lines
+            .iter()
+            .map(|line| line.as_str().map(str::to_owned))
+            .collect::<Option<_>>()
+            .ok_or(ItemStackDecodeError::InvalidKey("lore"))?;
+    }
+    if let Some(enchants) = meta.get("enchants") {
+        let JavaValue::Map(enchants) = enchants else {
+            return Err(ItemStackDecodeError::InvalidKey("enchants"));
+        };
+        for (enchantment, level) in enchants {
+            let enchantment = enchantment
+                .as_str()
+                .ok_or(ItemStackDecodeError::InvalidKey("enchants"))?;
+            item_stack
+                .enchantments
+                .insert(enchantment.to_owned(), integer(level, "enchants")?);
+        }
+    }
+    if let Some(custom_model_data) = meta.get("custom-model-data") {
+        item_stack.custom_model_data = Some(integer(custom_model_data, "custom-model-data")?);
+    }
+    Ok(item_stack)
+}
+
+fn integer(value: &JavaValue, key: &'static str) -> Result<i32, ItemStackDecodeError> {
+    value
+        .as_integer()
+        .and_then(|integer| i32::try_from(integer).ok())
+        .ok_or(ItemStackDecodeError::InvalidKey(key))
+}
+
+/// アイテム名や説明文を JSON のテキストコンポーネントとして読む
+///
+/// 1.13 以降の Bukkit は JSON のテキストコンポーネント、それより前は `§` で始まる
+/// 装飾コード付きの文字列で保存している。後者は装飾コードを含む文字列のコンポーネントにする。
+pub fn text_component(text: &str) -> serde_json::Value {
+    text.starts_with(['{', '[', '"'])
+        .then(|| serde_json::from_str(text).ok())
+        .flatten()
+        .unwrap_or_else(|| serde_json::Value::String(text.to_owned()))
+}
+
+/// アイテム名や説明文を、装飾を除いた文字列にする
+pub fn plain_text(text: &str) -> String {
+    let mut plain = String::new();
+    push_component_text(&text_component(text), &mut plain);
+    strip_formatting_codes(&plain)
+}
+
+fn push_component_text(component: &serde_json::Value, plain: &mut String) {
+    match component {
+        serde_json::Value::String(text) => plain.push_str(text),
+        serde_json::Value::Array(components) => {
+            for component in components {
+                push_component_text(component, plain);
+            }
+        }
+        serde_json::Value::Object(component) => {
+            if let Some(serde_json::Value::String(text)) = component.get("text") {
+                plain.push_str(text);
+            }
+            if let Some(extra) = component.get("extra") {
+                push_component_text(extra, plain);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn strip_formatting_codes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char == '§' {
+            chars.next();
+        } else {
+            stripped.push(char);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ItemStackDecodeError, decode_item_stack, plain_text};
+    use crate::domain::ItemStack;
+    use crate::give_command::item_id;
+    use std::collections::BTreeMap;
+
+    // SeichiAssist と同じ形式 (BukkitObjectOutputStream + Base64) を手元で再現して作ったもの。
+    // 実際の gachadata の dump から取り出した itemstack に置き換えること
+    const ENCHANTED_PICKAXE: &str = include_str!("../fixtures/item_stacks/enchanted_pickaxe.txt");
+    const PLAIN_DIAMOND: &str = include_str!("../fixtures/item_stacks/plain_diamond.txt");
+    const LEGACY_GOLDEN_APPLES: &str =
+        include_str!("../fixtures/item_stacks/legacy_golden_apples.txt");
+
+    #[test]
+    fn item_stacks_with_meta_are_decoded() {
+        let pickaxe = decode_item_stack(ENCHANTED_PICKAXE.as_bytes()).unwrap();
+        assert_eq!(pickaxe.material, "DIAMOND_PICKAXE");
+        assert_eq!(pickaxe.amount, 1);
+        assert_eq!(
+            plain_text(pickaxe.display_name.as_deref().unwrap()),
+            "ガチャリンゴのつるはし"
+        );
+        assert_eq!(
+            pickaxe
+                .lore
+                .iter()
+                .map(|line| plain_text(line))
+                .collect::<Vec<_>>(),
+            ["整地の達人が使っていた", "所有者: unchama"]
+        );
+        assert_eq!(
+            pickaxe.enchantments,
+            BTreeMap::from([
+                ("DIG_SPEED".to_owned(), 7),
+                ("DURABILITY".to_owned(), 3),
+                ("LOOT_BONUS_BLOCKS".to_owned(), 2),
+            ])
+        );
+        assert_eq!(pickaxe.custom_model_data, Some(12));
+
+        let apples = decode_item_stack(LEGACY_GOLDEN_APPLES.as_bytes()).unwrap();
+        assert_eq!(
+            apples,
+            ItemStack {
+                material: "GOLDEN_APPLE".to_owned(),
+                amount: 64,
+                // エンチャントされた金のリンゴ
+                damage: 1,
+                display_name: Some("§6§lがちゃりんご".to_owned()),
+                lore: vec![
+                    "§r§f食べると少しマナが回復する".to_owned(),
+                    "§r§7Rank: 1".to_owned(),
+                ],
+                enchantments: BTreeMap::new(),
+                custom_model_data: None,
+            }
+        );
+        assert_eq!(plain_text(&apples.lore[1]), "Rank: 1");
+        assert_eq!(
+            item_id(&apples).unwrap(),
+            "minecraft:enchanted_golden_apple"
+        );
+    }
+
+    #[test]
+    fn item_stacks_without_meta_have_defaults() {
+        let diamond = ItemStack {
+            material: "DIAMOND".to_owned(),
+            amount: 1,
+            damage: 0,
+            display_name: None,
+            lore: Vec::new(),
+            enchantments: BTreeMap::new(),
+            custom_model_data: None,
+        };
+        assert_eq!(
+            decode_item_stack(PLAIN_DIAMOND.as_bytes()).unwrap(),
+            diamond
+        );
+
+        let raw = base64::Engine::decode(
+            &base64::prelude::BASE64_STANDARD,
+            PLAIN_DIAMOND.replace('\n', ""),
+        )
+        .unwrap();
+        assert_eq!(
+            decode_item_stack(&raw).unwrap(),
+            diamond,
+            "Base64 にしていないストリームも読める"
+        );
+    }
+
+    #[test]
+    fn undecodable_item_stacks_are_rejected() {
+        assert!(matches!(
+            decode_item_stack(b"not base64!"),
+            Err(ItemStackDecodeError::Base64(_))
+        ));
+        assert!(matches!(
+            decode_item_stack(&PLAIN_DIAMOND.as_bytes()[..198]),
+            Err(ItemStackDecodeError::Stream(_))
+        ));
+        // writeInt(9) の後にすべてのスロットが null
+        let empty = [
+            0xac, 0xed, 0x00, 0x05, 0x77, 0x04, 0x00, 0x00, 0x00, 0x09, 0x70, 0x70,
+        ];
+        assert_eq!(decode_item_stack(&empty), Err(ItemStackDecodeError::Empty));
+    }
+}
@@ -0,0 +1,632 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+const STREAM_MAGIC: [u8; 4] = [0xac, 0xed, 0x00, 0x05];
+
+const TC_NULL: u8 = 0x70;
+const TC_REFERENCE: u8 = 0x71;
+const TC_CLASSDESC: u8 = 0x72;
+const TC_OBJECT: u8 = 0x73;
+const TC_STRING: u8 = 0x74;
+const TC_ARRAY: u8 = 0x75;
+const TC_CLASS: u8 = 0x76;
+const TC_BLOCKDATA: u8 = 0x77;
+const TC_ENDBLOCKDATA: u8 = 0x78;
+const TC_RESET: u8 = 0x79;
+const TC_BLOCKDATALONG: u8 = 0x7a;
+const TC_EXCEPTION: u8 = 0x7b;
+const TC_LONGSTRING: u8 = 0x7c;
+const TC_PROXYCLASSDESC: u8 = 0x7d;
+const TC_ENUM: u8 = 0x7e;
+
+/// 最初のオブジェクトに割り当てられるハンドル
+const BASE_WIRE_HANDLE: u32 = 0x7e_0000;
+
+const SC_WRITE_METHOD: u8 = 0x01;
+const SC_SERIALIZABLE: u8 = 0x02;
+const SC_EXTERNALIZABLE: u8 = 0x04;
+const SC_BLOCK_DATA: u8 = 0x08;
+
+/// 直列化されたオブジェクト
+///
+/// ボクシングされたプリミティブ型と、`java.util` と Guava のコレクション、
+/// Bukkit の `ConfigurationSerializable` を包む `org.bukkit.util.io.Wrapper` は
+/// 中身の値として読み取る。
+#[derive(Debug, Clone, PartialEq)]
+pub enum JavaValue {
+    Null,
+    Boolean(bool),
+    /// `byte`、`char`、`short`、`int`、`long`
+    Integer(i64),
+    /// `float`、`double`
+    Float(f64),
+    String(String),
+    /// 配列、`List`、`Set`
+    List(Vec<JavaValue>),
+    /// `Map` (エントリは直列化された順)
+    Map(Vec<(JavaValue, JavaValue)>),
+    Enum {
+        class: String,
+        constant: String,
+    },
+    Class(String),
+    /// 上記以外のオブジェクト (スーパークラスのフィールドも含む)
+    Object {
+        class: String,
+        fields: BTreeMap<String, JavaValue>,
+    },
+}
+
+impl JavaValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JavaValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            JavaValue::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    /// 文字列をキーとする `Map` から値を取り出す
+    pub fn get(&self, key: &str) -> Option<&JavaValue> {
+        match self {
+            JavaValue::Map(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key.as_str() == Some(key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+#[error("offset {offset}: {reason}")]
+pub struct JavaStreamError {
+    /// ストリームの先頭からのバイト位置
+    pub offset: usize,
+    pub reason: String,
+}
+
+/// `ObjectOutputStream` で書かれたストリームから、オブジェクトを書かれた順にすべて読み取る
+///
+/// `writeInt` などで書かれたプリミティブ値 (ブロックデータ) は読み飛ばす。
+/// `null` も `JavaValue::Null` として返す。
+pub fn read_objects(stream: &[u8]) -> Result<Vec<JavaValue>, JavaStreamError> {
+    let mut reader = Reader {
+        stream,
+        position: 0,
+        handles: Vec::new(),
+    };
+    if reader.bytes(STREAM_MAGIC.len())? != STREAM_MAGIC {
+        return Err(reader.error_at(0, "not a Java serialization stream"));
+    }
+
+    let mut objects = Vec::new();
+    while reader.position < stream.len() {
+        if let Some(object) = reader.content()? {
+            objects.push(object);
+        }
+    }
+    Ok(objects)
+}
+
+#[derive(Debug)]
+struct ClassDesc {
+    name: String,
+    flags: u8,
+    fields: Vec<FieldDesc>,
+    super_class: Option<Rc<ClassDesc>>,
+}
+
+impl ClassDesc {
+    /// スーパークラスから順に並べたクラス階層 (ストリーム上のクラスデータの順)
+    fn hierarchy(self: &Rc<Self>) -> Vec<Rc<ClassDesc>> {
+        let mut hierarchy = vec![Rc::clone(self)];
+        while let Some(super_class) = hierarchy.last().unwrap().super_class.clone() {
+            hierarchy.push(super_class);
+        }
+        hierarchy.reverse();
+        hierarchy
+    }
+}
+
+#[derive(Debug)]
+struct FieldDesc {
+    name: String,
+    /// `B`、`I`、`L` などのフィールドの型コード
+    type_code: u8,
+}
+
+enum Handle {
+    ClassDesc(Rc<ClassDesc>),
+    Value(JavaValue),
+    /// 読み取り中のオブジェクト (自身への参照は扱わない)
+    Pending,
+}
+
+struct Reader<'a> {
+    stream: &'a [u8],
+    position: usize,
+    handles: Vec<Handle>,
+}
+
+impl<'a> Reader<'a> {
+    fn error_at(&self, offset: usize, reason: impl Into<String>) -> JavaStreamError {
+        JavaStreamError {
+            offset,
+            reason: reason.into(),
+        }
+    }
+
+    fn error(&self, reason: impl Into<String>) -> JavaStreamError {
+        self.error_at(self.position, reason)
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], JavaStreamError> {
+        let bytes = self
+            .stream
+            .get(self.position..self.position + length)
+            .ok_or_else(|| self.error("unexpected end of stream"))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], JavaStreamError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, JavaStreamError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn peek(&self) -> Result<u8, JavaStreamError> {
+        self.stream
+            .get(self.position)
+            .copied()
+            .ok_or_else(|| self.error("unexpected end of stream"))
+    }
+
+    fn u16(&mut self) -> Result<u16, JavaStreamError> {
+        self.array().map(u16::from_be_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, JavaStreamError> {
+        self.array().map(i32::from_be_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64, JavaStreamError> {
+        self.array().map(i64::from_be_bytes)
+    }
+
+    /// `writeUTF` で書かれた文字列 (長さの後に modified UTF-8)
+    fn utf(&mut self, long: bool) -> Result<String, JavaStreamError> {
+        let start = self.position;
+        let length = if long {
+            usize::try_from(self.i64()?).map_err(|_| self.error_at(start, "negative length"))?
+        } else {
+            usize::from(self.u16()?)
+        };
+        let bytes = self.bytes(length)?;
+        decode_modified_utf8(bytes).ok_or_else(|| self.error_at(start, "invalid modified UTF-8"))
+    }
+
+    fn new_handle(&mut self, handle: Handle) -> usize {
+        self.handles.push(handle);
+        self.handles.len() - 1
+    }
+
+    fn reference(&mut self) -> Result<&Handle, JavaStreamError> {
+        let start = self.position;
+        let handle = u32::from_be_bytes(self.array()?);
+        handle
+            .checked_sub(BASE_WIRE_HANDLE)
+            .and_then(|index| self.handles.get(index as usize))
+            .ok_or_else(|| self.error_at(start, format!("unknown handle {handle:#x}")))
+    }
+
+    /// ストリームの要素を 1 つ読む。ブロックデータとリセットは読み飛ばして `None` を返す
+    fn content(&mut self) -> Result<Option<JavaValue>, JavaStreamError> {
+        match self.peek()? {
+            TC_BLOCKDATA => {
+                self.position += 1;
+                let length = usize::from(self.u8()?);
+                self.bytes(length)?;
+                Ok(None)
+            }
+            TC_BLOCKDATALONG => {
+                self.position += 1;
+                let start = self.position;
+                let length = usize::try_from(self.i32()?)
+                    .map_err(|_| self.error_at(start, "negative block data length"))?;
+                self.bytes(length)?;
+                Ok(None)
+            }
+            TC_RESET => {
+                self.position += 1;
+                self.handles.clear();
+                Ok(None)
+            }
+            _ => self.object().map(Some),
+        }
+    }
+
+    fn object(&mut self) -> Result<JavaValue, JavaStreamError> {
+        let start = self.position;
+        match self.u8()? {
+            TC_NULL => Ok(JavaValue::Null),
+            TC_REFERENCE => match self.reference()? {
+                Handle::Value(value) => Ok(value.clone()),
+                Handle::ClassDesc(_) => {
+                    Err(self.error_at(start, "reference to a class descriptor"))
+                }
+                Handle::Pending => {
+                    Err(self.error_at(start, "circular references are not supported"))
+                }
+            },
+            TC_OBJECT => self.new_object(),
+            TC_STRING | TC_LONGSTRING => {
+                let string = JavaValue::String(self.utf(self.stream[start] == TC_LONGSTRING)?);
+                self.new_handle(Handle::Value(string.clone()));
+                Ok(string)
+            }
+            TC_ARRAY => self.new_array(),
+            TC_CLASS => {
+                let class = JavaValue::Class(self.required_class_desc()?.name.clone());
+                self.new_handle(Handle::Value(class.clone()));
+                Ok(class)
+            }
+            TC_ENUM => {
+                let class_desc = self.required_class_desc()?;
+                let handle = self.new_handle(Handle::Pending);
+                let constant_start = self.position;
+                let JavaValue::String(constant) = self.object()? else {
+                    return Err(self.error_at(constant_start, "enum constant name is not a string"));
+                };
+                let value = JavaValue::Enum {
+                    class: class_desc.name.clone(),
+                    constant,
+                };
+                self.handles[handle] = Handle::Value(value.clone());
+                Ok(value)
+            }
+            TC_EXCEPTION => Err(self.error_at(start, "stream contains an exception")),
+            tag => Err(self.error_at(start, format!("unexpected type code {tag:#04x}"))),
+        }
+    }
+
+    fn class_desc(&mut self) -> Result<Option<Rc<ClassDesc>>, JavaStreamError> {
+        let start = self.position;
+        match self.u8()? {
+            TC_NULL => Ok(None),
+            TC_REFERENCE => match self.reference()? {
+                Handle::ClassDesc(class_desc) => Ok(Some(Rc::clone(class_desc))),
+                _ => Err(self.error_at(start, "reference is not a class descriptor")),
+            },
+            TC_CLASSDESC => {
+                let name = self.utf(false)?;
+                let _serial_version_uid = self.i64()?;
+                let handle = self.new_handle(Handle::Pending);
+                let flags = self.u8()?;
+                let field_count = self.u16()?;
+                let mut fields = Vec::with_capacity(field_count.into());
+                for _ in 0..field_count {
+                    let type_code = self.u8()?;
+                    let name = self.utf(false)?;
+                    if matches!(type_code, b'L' | b'[') {
+                        // フィールドの型名。値を読むときには使わない
+                        self.object()?;
+                    }
+                    fields.push(FieldDesc { name, type_code });
+                }
+                self.annotation()?;
+                let class_desc = Rc::new(ClassDesc {
+                    name,
+                    flags,
+                    fields,
+                    super_class: self.class_desc()?,
+                });
+                self.handles[handle] = Handle::ClassDesc(Rc::clone(&class_desc));
+                Ok(Some(class_desc))
+            }
+            TC_PROXYCLASSDESC => {
+                let handle = self.new_handle(Handle::Pending);
+                let interface_count = self.i32()?;
+                for _ in 0..interface_count {
+                    self.utf(false)?;
+                }
+                self.annotation()?;
+                let class_desc = Rc::new(ClassDesc {
+                    name: "$Proxy".to_owned(),
+                    flags: SC_SERIALIZABLE,
+                    fields: Vec::new(),
+                    super_class: self.class_desc()?,
+                });
+                self.handles[handle] = Handle::ClassDesc(Rc::clone(&class_desc));
+                Ok(Some(class_desc))
+            }
+            tag => Err(self.error_at(
+                start,
+                format!("expected a class descriptor, but was type code {tag:#04x}"),
+            )),
+        }
+    }
+
+    fn required_class_desc(&mut self) -> Result<Rc<ClassDesc>, JavaStreamError> {
+        let start = self.position;
+        self.class_desc()?
+            .ok_or_else(|| self.error_at(start, "class descriptor must not be null"))
+    }
+
+    /// `writeObject` などが書いた追加のデータを `TC_ENDBLOCKDATA` まで読み、含まれるオブジェクトを返す
+    fn annotation(&mut self) -> Result<Vec<JavaValue>, JavaStreamError> {
+        let mut objects = Vec::new();
+        while self.peek()? != TC_ENDBLOCKDATA {
+            if let Some(object) = self.content()? {
+                objects.push(object);
+            }
+        }
+        self.position += 1;
+        Ok(objects)
+    }
+
+    fn new_object(&mut self) -> Result<JavaValue, JavaStreamError> {
+        let class_desc = self.required_class_desc()?;
+        let handle = self.new_handle(Handle::Pending);
+        let mut fields = BTreeMap::new();
+        let mut annotation = Vec::new();
+        for class in class_desc.hierarchy() {
+            if class.flags & SC_EXTERNALIZABLE != 0 {
+                if class.flags & SC_BLOCK_DATA == 0 {
+                    return Err(self.error(format!(
+                        "externalizable class {} written without block data is not supported",
+                        class.name
+                    )));
+                }
+                annotation.extend(self.annotation()?);
+            } else if class.flags & SC_SERIALIZABLE != 0 {
+                for field in &class.fields {
+                    let value = self.value(field.type_code)?;
+                    fields.insert(field.name.clone(), value);
+                }
+                if class.flags & SC_WRITE_METHOD != 0 {
+                    annotation.extend(self.annotation()?);
+                }
+            }
+        }
+
+        let value = interpret(&class_desc, fields, annotation);
+        self.handles[handle] = Handle::Value(value.clone());
+        Ok(value)
+    }
+
+    fn new_array(&mut self) -> Result<JavaValue, JavaStreamError> {
+        let class_desc = self.required_class_desc()?;
+        let handle = self.new_handle(Handle::Pending);
+        let start = self.position;
+        let length = usize::try_from(self.i32()?)
+            .map_err(|_| self.error_at(start, "negative array length"))?;
+        // クラス名は `[I` や `[Ljava.lang.Object;` の形
+        let element_type = class_desc.name.as_bytes().get(1).copied().unwrap_or(b'L');
+        let elements = (0..length)
+            .map(|_| self.value(element_type))
+            .collect::<Result<_, _>>()?;
+
+        let value = JavaValue::List(elements);
+        self.handles[handle] = Handle::Value(value.clone());
+        Ok(value)
+    }
+
+    /// 型コードが `type_code` のフィールド (または配列の要素) の値
+    fn value(&mut self, type_code: u8) -> Result<JavaValue, JavaStreamError> {
+        Ok(match type_code {
+            b'B' => JavaValue::Integer(i8::from_be_bytes(self.array()?).into()),
+            b'C' => JavaValue::Integer(self.u16()?.into()),
+            b'S' => JavaValue::Integer(i16::from_be_bytes(self.array()?).into()),
+            b'I' => JavaValue::Integer(self.i32()?.into()),
+            b'J' => JavaValue::Integer(self.i64()?),
+            b'F' => JavaValue::Float(f32::from_be_bytes(self.array()?).into()),
+            b'D' => JavaValue::Float(f64::from_be_bytes(self.array()?)),
+            b'Z' => JavaValue::Boolean(self.u8()? != 0),
+            b'L' | b'[' => self.object()?,
+            _ => {
+                return Err(self.error(format!(
+                    "unknown field type code {:?}",
+                    char::from(type_code)
+                )));
+            }
+        })
+    }
+}
+
+/// よく使われるクラスのオブジェクトを、中身の値に置き換える
+fn interpret(
+    class_desc: &Rc<ClassDesc>,
+    mut fields: BTreeMap<String, JavaValue>,
+    annotation: Vec<JavaValue>,
+) -> JavaValue {
+    let hierarchy = class_desc.hierarchy();
+    let extends = |name: &str| hierarchy.iter().any(|class| class.name == name);
+    let name = class_desc.name.as_str();
+
+    match name {
+        "java.lang.Boolean"
+        | "java.lang.Byte"
+        | "java.lang.Character"
+        | "java.lang.Short"
+        | "java.lang.Integer"
+        | "java.lang.Long"
+        | "java.lang.Float"
+        | "java.lang.Double" => {
+            if let Some(value) = fields.remove("value") {
+                return value;
+            }
+        }
+        // ConfigurationSerializable (ItemStack や ItemMeta) を Map にしたもの
+        "org.bukkit.util.io.Wrapper" => {
+            if let Some(map) = fields.remove("map") {
+                return map;
+            }
+        }
+        _ => {}
+    }
+
+    // Guava の Immutable コレクションは SerializedForm に置き換えて直列化される
+    if name.starts_with("com.google.common.collect.") && name.ends_with("$SerializedForm") {
+        if let Some(JavaValue::List(elements)) = fields.get("elements") {
+            return JavaValue::List(elements.clone());
+        }
+        if let (Some(JavaValue::List(keys)), Some(JavaValue::List(values))) =
+            (fields.get("keys"), fields.get("values"))
+        {
+            return JavaValue::Map(keys.iter().cloned().zip(values.iter().cloned()).collect());
+        }
+    }
+
+    if extends("java.util.HashMap")
+        || extends("java.util.TreeMap")
+        || extends("java.util.Hashtable")
+    {
+        let mut entries = annotation.into_iter();
+        let mut map = Vec::new();
+        while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+            map.push((key, value));
+        }
+        return JavaValue::Map(map);
+    }
+    if extends("java.util.ArrayList")
+        || extends("java.util.LinkedList")
+        || extends("java.util.HashSet")
+        || extends("java.util.TreeSet")
+        || extends("java.util.ArrayDeque")
+    {
+        return JavaValue::List(annotation);
+    }
+
+    JavaValue::Object {
+        class: name.to_owned(),
+        fields,
+    }
+}
+
+/// `DataOutput.writeUTF` の modified UTF-8 (U+0000 は 2 バイト、補助文字はサロゲートペアを
+/// それぞれ 3 バイトで表す) を読む
+fn decode_modified_utf8(bytes: &[u8]) -> Option<String> {
+    let continuation = |index: usize| {
+        bytes
+            .get(index)
+            .filter(|&&byte| byte & 0xc0 == 0x80)
+            .map(|&byte| u16::from(byte & 0x3f))
+    };
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while let Some(&byte) = bytes.get(index) {
+        let byte = u16::from(byte);
+        match byte {
+            0x00..=0x7f => {
+                units.push(byte);
+                index += 1;
+            }
+            0xc0..=0xdf => {
+                units.push((byte & 0x1f) << 6 | continuation(index + 1)?);
+                index += 2;
+            }
+            0xe0..=0xef => {
+                units.push(
+                    (byte & 0x0f) << 12 | continuation(index + 1)? << 6 | continuation(index + 2)?,
+                );
+                index += 3;
+            }
+            _ => return None,
+        }
+    }
+    String::from_utf16(&units).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JavaStreamError, JavaValue, read_objects};
+    use base64::prelude::{BASE64_STANDARD, Engine};
+
+    #[test]
+    fn strings_are_read_as_modified_utf8_and_can_be_referenced() {
+        let stream = [
+            0xac, 0xed, 0x00, 0x05, // magic, version
+            0x74, 0x00, 0x09, // TC_STRING, 長さ 9
+            b'a', 0xc0, 0x80, // "a\0"
+            0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80, // U+1F600 (サロゲートペア)
+            0x70, // TC_NULL
+            0x71, 0x00, 0x7e, 0x00, 0x00, // TC_REFERENCE 0x7e0000
+        ];
+
+        let text = JavaValue::String("a\0😀".to_owned());
+        assert_eq!(
+            read_objects(&stream).unwrap(),
+            [text.clone(), JavaValue::Null, text]
+        );
+    }
+
+    #[test]
+    fn java_util_collections_and_boxed_primitives_are_read_as_values() {
+        // new LinkedHashMap<>(Map.of("list", new ArrayList<>(List.of(1L, "a", true)),
+        //                           "set", new HashSet<>(List.of(2.5))))
+        let stream = BASE64_STANDARD
+            .decode(
+                "rO0ABXNyABdqYXZhLnV0aWwuTGlua2VkSGFzaE1hcDTATlwQbMD7AgABWgALYWNjZXNzT3JkZXJ4cgAR\
+                 amF2YS51dGlsLkhhc2hNYXAFB9rBwxZg0QMAAkYACmxvYWRGYWN0b3JJAAl0aHJlc2hvbGR4cD9AAAAA\
+                 AAAMdwgAAAAQAAAAAnQABGxpc3RzcgATamF2YS51dGlsLkFycmF5TGlzdHiB0h2Zx2GdAwABSQAEc2l6\
+                 ZXhwAAAAA3cEAAAAA3NyAA5qYXZhLmxhbmcuTG9uZzuL5JDMjyPfAgABSgAFdmFsdWV4cgAQamF2YS5s\
+                 YW5nLk51bWJlcoaslR0LlOCLAgAAeHAAAAAAAAAAAXQAAWFzcgARamF2YS5sYW5nLkJvb2xlYW7NIHKA\
+                 1Zz67gIAAVoABXZhbHVleHABeHQAA3NldHNyABFqYXZhLnV0aWwuSGFzaFNldLpEhZWWuLc0AwAAeHB3\
+                 DAAAABA/QAAAAAAAAXNyABBqYXZhLmxhbmcuRG91YmxlgLPCSilr+wQCAAFEAAV2YWx1ZXhxAH4AB0AE\
+                 AAAAAAAAeHgA",
+            )
+            .unwrap();
+
+        let string = |string: &str| JavaValue::String(string.to_owned());
+        assert_eq!(
+            read_objects(&stream).unwrap(),
+            [JavaValue::Map(vec![
+                (
+                    string("list"),
+                    JavaValue::List(vec![
+                        JavaValue::Integer(1),
+                        string("a"),
+                        JavaValue::Boolean(true),
+                    ]),
+                ),
+                (string("set"), JavaValue::List(vec![JavaValue::Float(2.5)])),
+            ])]
+        );
+    }
+
+    #[test]
+    fn malformed_streams_are_rejected_with_their_offset() {
+        assert_eq!(
+            read_objects(b"rO0ABXNy"),
+            Err(JavaStreamError {
+                offset: 0,
+                reason: "not a Java serialization stream".to_owned(),
+            })
+        );
+        assert_eq!(
+            read_objects(&[0xac, 0xed, 0x00, 0x05, 0x74, 0x00, 0x05, b'a']),
+            Err(JavaStreamError {
+                offset: 7,
+                reason: "unexpected end of stream".to_owned(),
+            })
+        );
+        assert_eq!(
+            read_objects(&[0xac, 0xed, 0x00, 0x05, 0x71, 0x00, 0x7e, 0x00, 0x00]),
+            Err(JavaStreamError {
+                offset: 5,
+                reason: "unknown handle 0x7e0000".to_owned(),
+            })
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use crate::domain::GachaPrize;
+use crate::give_command::{MinecraftVersion, item_components, item_id, item_tag};
+use serde_json::json;
+use std::io::{Cursor, Write};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// データパックのルートテーブルの名前空間 (`/loot give @s loot gachadata:<名前>`)
+pub const NAMESPACE: &str = "gachadata";
+
+/// 確率を整数の重みにする倍率。確率は 100 万分の 1 単位に丸める
+const WEIGHT_SCALE: f64 = 1_000_000.0;
+
+/// 景品を 1 回引くルートテーブル (JSON) を作る
+///
+/// 各景品の重みは確率に比例させる。SeichiAssist はどの景品にも当たらなかった分 (確率の合計の
+/// 1 との差) をはずれとするため、それを空のエントリーとして加え、確率がそのまま一致するようにする。
+/// アイテムを読み取れなかった景品や、アイテム ID が分からない 1.13 より前のアイテムの景品も、
+/// 他の景品の確率を変えないよう空のエントリーにする。
+pub fn loot_table(prizes: &[&GachaPrize], version: MinecraftVersion) -> serde_json::Value {
+    let mut entries = Vec::new();
+    for prize in prizes {
+        let weight = weight(prize.probability);
+        if weight == 0 {
+            continue;
+        }
+        let Some((item, id)) = prize
+            .item
+            .as_ref()
+            .and_then(|item| Some((item, item_id(item).ok()?)))
+        else {
+            entries.push(json!({ "type": "minecraft:empty", "weight": weight }));
+            continue;
+        };
+
+        let mut functions = Vec::new();
+        if item.amount != 1 {
+            functions.push(json!({ "function": "minecraft:set_count", "count": item.amount }));
+        }
+        if let Some(tag) = item_tag(item, version) {
+            functions.push(json!({ "function": "minecraft:set_nbt", "tag": tag }));
+        }
+        if let Some(components) = item_components(item, version) {
+            functions
+                .push(json!({ "function": "minecraft:set_components", "components": components }));
+        }
+        let mut entry = json!({ "type": "minecraft:item", "name": id, "weight": weight });
+        if !functions.is_empty() {
+            entry["functions"] = functions.into();
+        }
+        entries.push(entry);
+    }
+
+    let total: f64 = prizes.iter().map(|prize| prize.probability.max(0.0)).sum();
+    let miss = weight(1.0 - total);
+    if miss > 0 {
+        entries.push(json!({ "type": "minecraft:empty", "weight": miss }));
+    }
+
+    json!({
+        "type": "minecraft:generic",
+        "pools": [{ "rolls": 1, "entries": entries }],
+    })
+}
+
+fn weight(probability: f64) -> i64 {
+    if probability <= 0.0 {
+        return 0;
+    }
+    // 100 万分の 1 未満でも当たる可能性はあるため、最小の重みを 1 とする
+    ((probability * WEIGHT_SCALE).round() as i64).max(1)
+}
+
+/// ルートテーブル `name` だけを含むデータパック (zip) を作る
+///
+/// 同じ内容からは同じバイト列になるよう、zip のファイルの日時は固定する。
+pub fn datapack(
+    name: &str,
+    loot_table: &serde_json::Value,
+    description: &str,
+    version: MinecraftVersion,
+) -> zip::result::ZipResult<Vec<u8>> {
+    let pack_mcmeta = json!({
+        "pack": {
+            "pack_format": pack_format(version),
+            "description": description,
+        }
+    });
+    // 1.21 でディレクトリ名が単数形になった
+    let directory = if version >= MinecraftVersion::new(1, 21, 0) {
+        "loot_table"
+    } else {
+        "loot_tables"
+    };
+
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default());
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("pack.mcmeta", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&pack_mcmeta).unwrap())?;
+    zip.start_file(format!("data/{NAMESPACE}/{directory}/{name}.json"), options)?;
+    zip.write_all(&serde_json::to_vec_pretty(loot_table).unwrap())?;
+    Ok(zip.finish()?.into_inner())
+}
+
+/// `version` のデータパックの `pack_format`。知っているものより新しいバージョンには最新の値を使う
+fn pack_format(version: MinecraftVersion) -> u32 {
+    const PACK_FORMATS: &[((u16, u16, u16), u32)] = &[
+        ((1, 21, 7), 81),
+        ((1, 21, 6), 80),
+        ((1, 21, 5), 71),
+        ((1, 21, 4), 61),
+        ((1, 21, 2), 57),
+        ((1, 21, 0), 48),
+        ((1, 20, 5), 41),
+        ((1, 20, 3), 26),
+        ((1, 20, 2), 18),
+        ((1, 20, 0), 15),
+        ((1, 19, 4), 12),
+        ((1, 19, 0), 10),
+        ((1, 18, 2), 9),
+        ((1, 18, 0), 8),
+        ((1, 17, 0), 7),
+        ((1, 16, 2), 6),
+        ((1, 15, 0), 5),
+    ];
+    PACK_FORMATS
+        .iter()
+        .find(|((major, minor, patch), _)| version >= MinecraftVersion::new(*major, *minor, *patch))
+        .map_or(4, |(_, pack_format)| *pack_format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{datapack, loot_table};
+    use crate::domain::{GachaPrize, ItemStack};
+    use crate::give_command::MinecraftVersion;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::io::{Cursor, Read};
+
+    fn prize(id: i32, probability: f64, item: Option<ItemStack>) -> GachaPrize {
+        GachaPrize {
+            id,
+            probability,
+            itemstack: None,
+            item,
+            event_id: None,
+        }
+    }
+
+    fn item(material: &str, amount: i32, display_name: Option<&str>) -> ItemStack {
+        ItemStack {
+            material: material.to_owned(),
+            amount,
+            damage: 0,
+            display_name: display_name.map(str::to_owned),
+            lore: Vec::new(),
+            enchantments: BTreeMap::new(),
+            custom_model_data: None,
+        }
+    }
+
+    #[test]
+    fn weights_match_probabilities_and_the_rest_is_a_miss() {
+        let prizes = [
+            prize(1, 0.1, Some(item("DIAMOND", 1, None))),
+            prize(2, 0.0000001, Some(item("STONE", 64, Some("§6石")))),
+            prize(3, 0.2, None),
+        ];
+        let prizes: Vec<_> = prizes.iter().collect();
+
+        assert_eq!(
+            loot_table(&prizes, "1.18.2".parse().unwrap()),
+            json!({
+                "type": "minecraft:generic",
+                "pools": [{
+                    "rolls": 1,
+                    "entries": [
+                        { "type": "minecraft:item", "name": "minecraft:diamond", "weight": 100_000 },
+                        {
+                            "type": "minecraft:item",
+                            "name": "minecraft:stone",
+                            "weight": 1,
+                            "functions": [
+                                { "function": "minecraft:set_count", "count": 64 },
+                                { "function": "minecraft:set_nbt", "tag": r#"{display:{Name:'"§6石"'}}"# },
+                            ],
+                        },
+                        { "type": "minecraft:empty", "weight": 200_000 },
+                        { "type": "minecraft:empty", "weight": 700_000 },
+                    ],
+                }],
+            })
+        );
+
+        let table = loot_table(&prizes, "1.21".parse().unwrap());
+        assert_eq!(
+            table["pools"][0]["entries"][1]["functions"][1],
+            json!({
+                "function": "minecraft:set_components",
+                "components": { "minecraft:custom_name": r#""§6石""# },
+            })
+        );
+    }
+
+    #[test]
+    fn legacy_items_use_modern_ids_or_become_empty_entries() {
+        let legacy = |material: &str, damage| ItemStack {
+            damage,
+            ..item(material, 1, None)
+        };
+        let prizes = [
+            prize(1, 0.5, Some(legacy("GOLDEN_APPLE", 1))),
+            prize(2, 0.5, Some(legacy("LOG", 2))),
+        ];
+        let prizes: Vec<_> = prizes.iter().collect();
+
+        assert_eq!(
+            loot_table(&prizes, "1.21".parse().unwrap())["pools"][0]["entries"],
+            json!([
+                { "type": "minecraft:item", "name": "minecraft:enchanted_golden_apple", "weight": 500_000 },
+                { "type": "minecraft:empty", "weight": 500_000 },
+            ])
+        );
+    }
+
+    #[test]
+    fn datapack_contains_pack_mcmeta_and_the_loot_table() {
+        let table = json!({ "pools": [] });
+        let read = |version: &str| {
+            let version: MinecraftVersion = version.parse().unwrap();
+            let zip = datapack("gacha-event-1", &table, "正月", version).unwrap();
+            let mut archive = zip::ZipArchive::new(Cursor::new(zip)).unwrap();
+            let mut files = BTreeMap::new();
+            for index in 0..archive.len() {
+                let mut file = archive.by_index(index).unwrap();
+                let mut content = String::new();
+                file.read_to_string(&mut content).unwrap();
+                files.insert(file.name().to_owned(), content);
+            }
+            files
+        };
+
+        let files = read("1.18.2");
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [
+                "data/gachadata/loot_tables/gacha-event-1.json",
+                "pack.mcmeta"
+            ]
+        );
+        let pack_mcmeta: serde_json::Value = serde_json::from_str(&files["pack.mcmeta"]).unwrap();
+        assert_eq!(
+            pack_mcmeta,
+            json!({ "pack": { "pack_format": 9, "description": "正月" } })
+        );
+
+        let files = read("1.21.1");
+        assert!(files.contains_key("data/gachadata/loot_table/gacha-event-1.json"));
+        assert!(files["pack.mcmeta"].contains("\"pack_format\": 48"));
+
+        assert_eq!(
+            datapack("a", &table, "", "1.20.4".parse().unwrap()).unwrap(),
+            datapack("a", &table, "", "1.20.4".parse().unwrap()).unwrap(),
+            "同じ内容からは同じ zip になる"
+        );
+    }
+}
@@ -1,180 +1,2434 @@
+mod analysis_api;
+mod change_probe;
+mod changelog;
+mod dump_diff;
+mod dump_history;
+mod dump_normalization;
+mod dump_validation;
+mod gacha_api;
+mod gacha_lint;
+mod gacha_parser;
+mod gacha_simulation;
+mod give_api;
+mod give_command;
+mod item_stack;
+mod java_serialization;
 mod logging;
+mod loot_table;
+mod native_dump;
 mod panic_hook;
+mod snapshot_store;
+mod sql_dump;
 mod telemetry;
+mod version_api;
 
 mod domain {
     use bytes::Bytes;
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
     use std::fmt::Debug;
-    use std::time::SystemTime;
+    use std::ops::Sub;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[derive(Clone, Default)]
+    pub struct GachadataDump(pub Bytes);
+
+    // dump の中身 (SQL 全文) を Debug 出力に含めない
+    impl Debug for GachadataDump {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("GachadataDump")
+                .field("len_bytes", &self.0.len())
+                .finish()
+        }
+    }
+
+    /// 正規化した dump の SHA-256 (16 進小文字)。dump のバージョンとして使う
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub struct ContentHash(pub String);
+
+    impl ContentHash {
+        pub fn of(dump: &[u8]) -> Self {
+            ContentHash(format!("{:x}", Sha256::digest(dump)))
+        }
+    }
+
+    impl std::fmt::Display for ContentHash {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct GachadataDumpWithTime {
+        pub dump: GachadataDump,
+        /// `dump` のバージョン。一度も dump できていなければ `None`
+        pub content_hash: Option<ContentHash>,
+        pub dump_time: Option<SystemTime>,
+        /// dump 元のデータベース名
+        pub database: String,
+        /// dump に含まれるテーブル
+        pub tables: Vec<String>,
+        /// テーブルごとの行数
+        pub row_counts: BTreeMap<String, u64>,
+        /// 最後に dump 元テーブルの変更を確認した結果
+        pub change_probe: Option<ChangeProbe>,
+        /// この dump の取得後に更新を試みて失敗した場合、その記録
+        /// (成功した更新で dump ごと置き換わるまで残る)
+        pub last_refresh_failure: Option<RefreshFailure>,
+        /// 前回から大きく減ったため公開を保留している、より新しい dump
+        pub held_back: Option<Box<HeldBackDump>>,
+        /// `dump` から読み取ったガチャの景品とイベント。読み取れなかった場合は `None`
+        pub gacha: Option<Arc<GachaData>>,
+    }
+
+    /// `gachadata` の 1 行 (ガチャの景品)
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct GachaPrize {
+        pub id: i32,
+        /// 1 回引いたときにこの景品が出る確率
+        pub probability: f64,
+        /// シリアライズされた Bukkit の ItemStack
+        pub itemstack: Option<Bytes>,
+        /// `itemstack` を読み取ったもの。`itemstack` が `NULL` か、読み取れなかった場合は `None`
+        pub item: Option<ItemStack>,
+        /// 景品が属するガチャイベント (`gacha_events.id`)。通常のガチャの景品なら `None`
+        pub event_id: Option<i32>,
+    }
+
+    /// 景品のアイテム (Bukkit の ItemStack のうち、景品の一覧に出す情報)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ItemStack {
+        /// Bukkit の `Material` の名前 (`DIAMOND_PICKAXE` など)
+        pub material: String,
+        pub amount: i32,
+        /// 1.13 より前の data value (`damage`)。書き出されていなければ 0
+        ///
+        /// ツールや防具では耐久値の減少量、それ以外では `GOLDEN_APPLE` と
+        /// エンチャントされた金のリンゴのような種類を表す。
+        pub damage: i16,
+        /// 保存されたままの表示名 (1.13 以降は JSON のテキストコンポーネント、
+        /// それより前は `§` の装飾コード付きの文字列)
+        pub display_name: Option<String>,
+        /// 保存されたままの説明文 (形式は `display_name` と同じ)
+        pub lore: Vec<String>,
+        /// Bukkit のエンチャント名 (`DIG_SPEED` など) ごとのレベル
+        pub enchantments: BTreeMap<String, i32>,
+        pub custom_model_data: Option<i32>,
+    }
+
+    /// `gacha_events` の 1 行 (期間限定のガチャイベント)
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct GachaEvent {
+        pub id: i32,
+        pub name: String,
+        /// 開始日時 (MySQL の `DATETIME` のまま。タイムゾーンは SeichiAssist の設定による)
+        pub start_time: jiff::civil::DateTime,
+        /// 終了日時 (`start_time` と同じく `DATETIME` のまま)
+        pub end_time: jiff::civil::DateTime,
+    }
+
+    /// dump から読み取ったガチャの景品とイベント
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct GachaData {
+        pub prizes: Vec<GachaPrize>,
+        pub events: Vec<GachaEvent>,
+    }
+
+    /// dump 元テーブルの変更確認 (`CHECKSUM TABLE`) の結果
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ChangeProbe {
+        pub probed_at: SystemTime,
+        /// テーブルごとのチェックサム
+        pub checksums: BTreeMap<String, u64>,
+    }
+
+    /// dump の更新に失敗した記録
+    #[derive(Debug, Clone)]
+    pub struct RefreshFailure {
+        pub failed_at: SystemTime,
+        pub reason: String,
+    }
+
+    /// 前回の dump からの減少
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Shrinkage {
+        /// `size_bytes` (dump のサイズ) または `rows.<テーブル名>` (テーブルの行数)
+        pub subject: String,
+        pub previous: u64,
+        pub current: u64,
+    }
+
+    impl std::fmt::Display for Shrinkage {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}: {} -> {}", self.subject, self.previous, self.current)
+        }
+    }
+
+    /// 過去に公開した dump
+    #[derive(Debug, Clone)]
+    pub struct DumpVersion {
+        pub content_hash: ContentHash,
+        /// この dump を公開した日時
+        pub published_at: SystemTime,
+        pub dump: GachadataDump,
+        pub row_counts: BTreeMap<String, u64>,
+        /// 直前に公開したバージョンからの変更点。直前のバージョンが履歴になければ `None`
+        pub changelog: Option<Changelog>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Changelog {
+        pub previous_version: ContentHash,
+        /// 「追加: …」「確率変更: …」のような変更点の説明
+        pub changes: Vec<String>,
+    }
+
+    /// 管理者に承認されるまで公開を保留している dump
+    #[derive(Debug, Clone)]
+    pub struct HeldBackDump {
+        pub snapshot: GachadataDumpWithTime,
+        /// 保留した理由 (公開中の dump からの減少)
+        pub shrinkage: Vec<Shrinkage>,
+    }
+
+    impl GachadataDumpWithTime {
+        /// 最終 dump の取得からの経過時間
+        pub fn age(&self) -> Option<Duration> {
+            self.dump_time
+                .map(|dump_time| dump_time.elapsed().unwrap_or_default())
+        }
+
+        /// 最終 dump の取得から `ttl` 以上経過しているかどうか
+        pub fn is_older_than(&self, ttl: Duration) -> bool {
+            match self.dump_time {
+                Some(dump_time) => SystemTime::now().sub(ttl) > dump_time,
+                None => true, // dump_timeがNoneになるのは起動して一度も取得されていないときのみ
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    pub trait GachaDataRepository: Debug + Sync + Send + 'static {
+        /// キャッシュ済みの dump が更新期限を過ぎていれば dump を更新する
+        async fn update_gachadata(&self) -> anyhow::Result<()>;
+
+        /// 更新期限に関わらず dump を更新する
+        async fn refresh_gachadata(&self) -> anyhow::Result<()>;
+
+        /// キャッシュ済みの dump が更新期限を過ぎているかどうか
+        fn is_gachadata_expired(&self) -> bool;
+
+        /// キャッシュ済みの dump が許容できる最大の古さを過ぎているかどうか
+        fn is_gachadata_past_max_age(&self) -> bool;
+
+        /// キャッシュ済みの dump を返す
+        fn gachadata_dump(&self) -> anyhow::Result<GachadataDumpWithTime>;
+
+        /// バージョンが `version` の dump を保留していれば公開し、承認した dump を返す。
+        /// 保留中の dump がないかバージョンが異なれば `None`
+        async fn approve_held_back_gachadata(
+            &self,
+            version: &ContentHash,
+        ) -> anyhow::Result<Option<HeldBackDump>>;
+
+        /// 履歴に残っている dump を新しい順に返す
+        fn gachadata_versions(&self) -> anyhow::Result<Vec<DumpVersion>>;
+
+        /// 履歴に残っている、バージョンが `id` の dump を返す
+        fn gachadata_version(&self, id: &ContentHash) -> anyhow::Result<Option<DumpVersion>>;
+    }
+}
+
+mod infra_repository_impls {
+    use crate::change_probe::{checksum_table_statement, checksum_tables, parse_checksum_output};
+    use crate::changelog::changelog_between;
+    use crate::config::{Dump, MySQL};
+    use crate::domain::{
+        ChangeProbe, Changelog, ContentHash, DumpVersion, GachaData, GachaDataRepository,
+        GachadataDump, GachadataDumpWithTime, HeldBackDump, RefreshFailure, Shrinkage,
+    };
+    use crate::dump_history::{DumpHistory, Retention};
+    use crate::dump_normalization::normalize_dump;
+    use crate::dump_validation::{DumpStats, DumpValidationError, detect_shrinkage, validate_dump};
+    use crate::gacha_lint::lint;
+    use crate::gacha_parser::{ParsedGachaData, parse_gacha_data};
+    use crate::native_dump::{DumpTarget, dump_tables};
+    use crate::snapshot_store::SnapshotStore;
+    use anyhow::{Context, anyhow, ensure};
+    use bytes::Bytes;
+    use futures_util::future::{BoxFuture, FutureExt, Shared};
+    use opentelemetry::KeyValue;
+    use opentelemetry::metrics::Counter;
+    use std::collections::BTreeMap;
+    use std::process::{ExitStatus, Output};
+    use std::sync::{Arc, LazyLock, Mutex};
+    use std::time::{Duration, SystemTime};
+    use tokio::process::Command;
+    use tracing::{Instrument, Span};
+
+    /// dump の取得に失敗した理由
+    ///
+    /// いずれの場合もキャッシュ済みの dump は置き換えない。
+    #[derive(Debug, thiserror::Error)]
+    pub enum DumpError {
+        #[error("failed to spawn mariadb-dump: {0}")]
+        Spawn(#[source] std::io::Error),
+        #[error("mariadb-dump exited with {status}: {stderr}")]
+        NonZeroExit { status: ExitStatus, stderr: String },
+        #[error("native dump failed: {0:#}")]
+        Native(#[source] anyhow::Error),
+        #[error("dump did not finish within {0:?}")]
+        Timeout(Duration),
+        #[error("dump task failed: {0}")]
+        TaskFailed(#[source] tokio::task::JoinError),
+        #[error("dump output is empty")]
+        EmptyOutput,
+        #[error("dump is incomplete: {0}")]
+        Invalid(#[from] DumpValidationError),
+        #[error(
+            "dump {version} shrank beyond DUMP_MAX_SHRINK_RATIO and is held back until approved ({})",
+            .shrinkage.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        )]
+        HeldBack {
+            version: ContentHash,
+            shrinkage: Vec<Shrinkage>,
+        },
+        #[error("dump {version} is the same as the held-back dump and stays held back")]
+        StillHeldBack { version: ContentHash },
+        #[error("failed to lock gachadata dump")]
+        LockPoisoned,
+    }
+
+    impl DumpError {
+        /// ログ検索用の短い識別子
+        pub fn kind(&self) -> &'static str {
+            match self {
+                DumpError::Spawn(_) => "spawn",
+                DumpError::NonZeroExit { .. } => "non_zero_exit",
+                DumpError::Native(_) => "native",
+                DumpError::Timeout(_) => "timeout",
+                DumpError::TaskFailed(_) => "task_failed",
+                DumpError::EmptyOutput => "empty_output",
+                DumpError::Invalid(_) => "invalid",
+                DumpError::HeldBack { .. } => "held_back",
+                DumpError::StillHeldBack { .. } => "still_held_back",
+                DumpError::LockPoisoned => "lock_poisoned",
+            }
+        }
+
+        /// 失敗を構造化フィールド付きの ERROR イベントとして記録し、
+        /// 現在の span (`run_gachadata_dump`) にも失敗理由を残す
+        pub fn log(&self) {
+            let span = Span::current();
+            span.record("dump.error.kind", self.kind());
+            if let DumpError::Timeout(_) = self {
+                span.record("dump.timed_out", true);
+            }
+
+            match self {
+                DumpError::NonZeroExit { status, stderr } => tracing::error!(
+                    dump.error.kind = self.kind(),
+                    dump.exit_code = status.code(),
+                    dump.stderr = %stderr,
+                    "gachadata dump failed: {self}"
+                ),
+                // 人の判断が必要なため alert=true を付けてアラートの対象にする
+                DumpError::HeldBack { shrinkage, version } => tracing::error!(
+                    alert = true,
+                    dump.content_hash = %version,
+                    dump.error.kind = self.kind(),
+                    dump.shrinkage = ?shrinkage,
+                    "gachadata dump held back: {self}"
+                ),
+                // 保留したときに通知済みなので、アラートを繰り返さない
+                DumpError::StillHeldBack { version } => tracing::info!(
+                    dump.content_hash = %version,
+                    dump.error.kind = self.kind(),
+                    "gachadata dump is still held back: {self}"
+                ),
+                _ => tracing::error!(
+                    dump.error.kind = self.kind(),
+                    "gachadata dump failed: {self}"
+                ),
+            }
+        }
+    }
+
+    /// dump の future を `timeout` 以内に完了させる
+    ///
+    /// 時間切れの場合は future を drop する。子プロセスは `kill_on_drop` により、
+    /// MySQL 接続は drop により切断される。
+    async fn with_timeout<T>(
+        timeout: Duration,
+        dump: impl Future<Output = Result<T, DumpError>>,
+    ) -> Result<T, DumpError> {
+        tokio::time::timeout(timeout, dump)
+            .await
+            .unwrap_or(Err(DumpError::Timeout(timeout)))
+    }
+
+    /// `mariadb-dump` の実行結果を検査し、成功していれば stdout を返す
+    fn check_mariadb_dump_output(output: Output) -> Result<Vec<u8>, DumpError> {
+        if !output.status.success() {
+            return Err(DumpError::NonZeroExit {
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            });
+        }
+        if output.stdout.is_empty() {
+            return Err(DumpError::EmptyOutput);
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// dump から景品とイベントを読み取る
+    ///
+    /// 読み取れなくても SQL の dump はそのまま公開できるため、警告を出して `None` とする。
+    /// 不正な値のため読み飛ばした行と、アイテムを読み取れなかった景品があれば、その位置や ID も警告する。
+    fn parse_gacha(dump: &[u8]) -> Option<Arc<GachaData>> {
+        match parse_gacha_data(dump) {
+            Ok(ParsedGachaData {
+                gacha,
+                skipped_rows,
+            }) => {
+                for error in skipped_rows {
+                    tracing::warn!(%error, "不正な値を含む行を読み飛ばしました");
+                }
+                let undecodable_items: Vec<_> = gacha
+                    .prizes
+                    .iter()
+                    .filter(|prize| prize.itemstack.is_some() && prize.item.is_none())
+                    .map(|prize| prize.id)
+                    .collect();
+                if !undecodable_items.is_empty() {
+                    tracing::warn!(
+                        prize_ids = ?undecodable_items,
+                        "景品のアイテム (gachadata.itemstack) を読み取れませんでした"
+                    );
+                }
+                Some(Arc::new(gacha))
+            }
+            Err(error) => {
+                tracing::warn!(%error, "dump から景品とイベントを読み取れませんでした");
+                None
+            }
+        }
+    }
+
+    /// 前回から大きく減ったため公開を保留した dump の数
+    ///
+    /// 最初に保留したときに作るため、[`crate::telemetry`] の初期化後の MeterProvider を使う。
+    static HELD_BACK_DUMPS: LazyLock<Counter<u64>> = LazyLock::new(|| {
+        opentelemetry::global::meter("gachadata-server")
+            .u64_counter("gachadata.dump.held_back")
+            .with_description("前回から大きく減ったため公開を保留した dump の数")
+            .build()
+    });
+
+    /// dump の中身を検証・正規化してからキャッシュを置き換える
+    ///
+    /// 検証に失敗した場合はキャッシュ済みの dump をそのまま残す。
+    /// 公開中の dump から `DUMP_MAX_SHRINK_RATIO` を超えて減っている場合は、
+    /// 管理者が承認するまで [`HeldBackDump`] として保留する。
+    /// 保留中の dump と同じ内容であれば、保留し直さずに [`DumpError::StillHeldBack`] を返す。
+    fn store_dump(
+        dump: &Mutex<GachadataDumpWithTime>,
+        source: &MySQL,
+        settings: &Dump,
+        change_probe: Option<ChangeProbe>,
+        bytes: impl Into<Bytes>,
+    ) -> Result<(), DumpError> {
+        let bytes = bytes.into();
+        if bytes.is_empty() {
+            return Err(DumpError::EmptyOutput);
+        }
+        let DumpStats { row_counts } = validate_dump(&bytes, &source.tables)?;
+        let bytes = Bytes::from(normalize_dump(&bytes));
+        let content_hash = ContentHash::of(&bytes);
+        tracing::info!(
+            dump.size_bytes = bytes.len(),
+            dump.row_counts = ?row_counts,
+            dump.content_hash = %content_hash,
+            "gachadata dump validated"
+        );
+
+        let gacha = parse_gacha(&bytes);
+
+        let Ok(mut dump) = dump.lock() else {
+            return Err(DumpError::LockPoisoned);
+        };
+        let snapshot = GachadataDumpWithTime {
+            dump: GachadataDump(bytes),
+            content_hash: Some(content_hash.clone()),
+            dump_time: Some(SystemTime::now()),
+            database: source.database.clone(),
+            tables: source.tables.clone(),
+            row_counts,
+            change_probe,
+            last_refresh_failure: None,
+            held_back: None,
+            gacha,
+        };
+
+        if !dump.dump.0.is_empty() {
+            let shrinkage = detect_shrinkage(
+                dump.dump.0.len() as u64,
+                &dump.row_counts,
+                snapshot.dump.0.len() as u64,
+                &snapshot.row_counts,
+                settings.max_shrink_ratio,
+            );
+            if !shrinkage.is_empty() {
+                // dump の取得自体には成功しているので、以前の失敗の記録は残さない
+                dump.last_refresh_failure = None;
+                if let Some(held_back) = &mut dump.held_back
+                    && held_back.snapshot.content_hash.as_ref() == Some(&content_hash)
+                {
+                    held_back.snapshot.change_probe = snapshot.change_probe;
+                    return Err(DumpError::StillHeldBack {
+                        version: content_hash,
+                    });
+                }
+                HELD_BACK_DUMPS.add(1, &[KeyValue::new("database", source.database.clone())]);
+                let version = content_hash.clone();
+                dump.held_back = Some(Box::new(HeldBackDump {
+                    snapshot,
+                    shrinkage: shrinkage.clone(),
+                }));
+                return Err(DumpError::HeldBack { version, shrinkage });
+            }
+        }
+
+        *dump = snapshot;
+        Ok(())
+    }
+
+    /// dump 元テーブルが前回の dump から変わっていなければ、dump を取り直さずに
+    /// `dump_time` だけを更新して `true` を返す
+    fn extend_if_unchanged(
+        dump: &Mutex<GachadataDumpWithTime>,
+        change_probe: &ChangeProbe,
+    ) -> Result<bool, DumpError> {
+        let mut dump = dump.lock().map_err(|_| DumpError::LockPoisoned)?;
+        let unchanged = !dump.dump.0.is_empty()
+            && dump
+                .change_probe
+                .as_ref()
+                .is_some_and(|previous| previous.checksums == change_probe.checksums);
+        if unchanged {
+            dump.dump_time = Some(change_probe.probed_at);
+            dump.change_probe = Some(change_probe.clone());
+            dump.last_refresh_failure = None;
+            // 公開中の dump と同じ内容に戻ったので、保留していた dump はもう不要
+            dump.held_back = None;
+        }
+        Ok(unchanged)
+    }
+
+    /// dump 元テーブルが保留中の dump から変わっていなければ `true` を返す
+    ///
+    /// 保留中の dump を取り直しても同じ内容になるだけなので、dump を省略させる。
+    fn is_still_held_back(
+        dump: &Mutex<GachadataDumpWithTime>,
+        change_probe: &ChangeProbe,
+    ) -> Result<bool, DumpError> {
+        let mut dump = dump.lock().map_err(|_| DumpError::LockPoisoned)?;
+        let Some(held_back) = &mut dump.held_back else {
+            return Ok(false);
+        };
+        let unchanged = held_back
+            .snapshot
+            .change_probe
+            .as_ref()
+            .is_some_and(|previous| previous.checksums == change_probe.checksums);
+        if unchanged {
+            held_back.snapshot.change_probe = Some(change_probe.clone());
+            dump.last_refresh_failure = None;
+        }
+        Ok(unchanged)
+    }
+
+    /// 保留中の dump のバージョンが `version` であれば公開中の dump と置き換える
+    ///
+    /// 管理者が確認した後に別の dump が保留された場合に、確認していない dump を
+    /// 公開しないようバージョンを照合する。
+    fn approve_held_back(
+        dump: &Mutex<GachadataDumpWithTime>,
+        version: &ContentHash,
+    ) -> anyhow::Result<Option<HeldBackDump>> {
+        let mut dump = dump
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock gachadata dump."))?;
+        let Some(held_back) = dump
+            .held_back
+            .take_if(|held_back| held_back.snapshot.content_hash.as_ref() == Some(version))
+        else {
+            return Ok(None);
+        };
+
+        tracing::warn!(
+            dump.content_hash = %version,
+            dump.shrinkage = ?held_back.shrinkage,
+            dump.row_counts = ?held_back.snapshot.row_counts,
+            "保留していた dump が承認されたため公開します"
+        );
+        *dump = held_back.snapshot.clone();
+        Ok(Some(*held_back))
+    }
+
+    /// 更新の失敗をキャッシュ済みの dump に記録する (dump 自体は置き換えない)
+    fn record_refresh_failure(dump: &Mutex<GachadataDumpWithTime>, error: &DumpError) {
+        if let Ok(mut dump) = dump.lock() {
+            dump.last_refresh_failure = Some(RefreshFailure {
+                failed_at: SystemTime::now(),
+                reason: error.to_string(),
+            });
+        }
+    }
+
+    fn is_dump_older_than(dump: &Mutex<GachadataDumpWithTime>, ttl: Duration) -> bool {
+        match dump.lock() {
+            Ok(dump) => dump.is_older_than(ttl),
+            _ => false,
+        }
+    }
+
+    type InFlightDump = Shared<BoxFuture<'static, Result<(), Arc<DumpError>>>>;
+
+    /// 実行中の dump を 1 つに制限し、同時に来た呼び出しにはその結果を共有する
+    ///
+    /// dump は `tokio::spawn` した task で実行するため、最初の呼び出し元
+    /// (HTTP リクエスト) が切断されても待っている他の呼び出し元の dump は中断されない。
+    #[derive(Clone, Default)]
+    pub struct SingleFlight {
+        in_flight: Arc<Mutex<Option<InFlightDump>>>,
+    }
+
+    impl std::fmt::Debug for SingleFlight {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let in_flight = self
+                .in_flight
+                .lock()
+                .map(|in_flight| in_flight.is_some())
+                .ok();
+            f.debug_struct("SingleFlight")
+                .field("in_flight", &in_flight)
+                .finish()
+        }
+    }
+
+    impl SingleFlight {
+        /// 実行中の dump があればその結果を待ち、なければ `dump` を開始してその結果を待つ
+        pub async fn run<F>(&self, dump: F) -> Result<(), Arc<DumpError>>
+        where
+            F: Future<Output = Result<(), DumpError>> + Send + 'static,
+        {
+            let in_flight = {
+                let mut slot = self
+                    .in_flight
+                    .lock()
+                    .map_err(|_| Arc::new(DumpError::LockPoisoned))?;
+                match slot.as_ref() {
+                    Some(in_flight) => in_flight.clone(),
+                    None => {
+                        let slot_handle = Arc::clone(&self.in_flight);
+                        let task = tokio::spawn(dump.in_current_span());
+                        let in_flight = async move {
+                            let result = match task.await {
+                                Ok(result) => result.map_err(Arc::new),
+                                Err(join_error) => Err(Arc::new(DumpError::TaskFailed(join_error))),
+                            };
+                            // dump の保存後に空けるので、後続の呼び出しは更新済みの dump を見る
+                            if let Ok(mut slot) = slot_handle.lock() {
+                                *slot = None;
+                            }
+                            result
+                        }
+                        .boxed()
+                        .shared();
+                        *slot = Some(in_flight.clone());
+                        in_flight
+                    }
+                }
+            };
+
+            in_flight.await
+        }
+    }
+
+    fn cloned_dump(dump: &Mutex<GachadataDumpWithTime>) -> anyhow::Result<GachadataDumpWithTime> {
+        dump.lock()
+            .map(|dump| dump.clone())
+            .map_err(|_| anyhow!("Failed to lock gachadata dump."))
+    }
+
+    /// dump の取得方法によらない、接続情報・設定・キャッシュ済みの dump・履歴
+    #[derive(Debug, Clone)]
+    pub struct DumpState {
+        pub connection_information: MySQL,
+        pub settings: Dump,
+        pub dump: Arc<Mutex<GachadataDumpWithTime>>,
+        pub refresh: SingleFlight,
+        /// 公開した dump の保存先 (`DUMP_SNAPSHOT_DIR` 未設定なら `None`)
+        pub snapshot_store: Option<SnapshotStore>,
+        pub history: Arc<Mutex<DumpHistory>>,
+    }
+
+    impl DumpState {
+        /// `DUMP_SNAPSHOT_DIR` が設定されていれば、保存済みの dump と履歴を読み込む
+        pub fn new(connection_information: MySQL, settings: Dump) -> Self {
+            let snapshot_store = settings.snapshot_dir.clone().map(SnapshotStore::new);
+            let dump = restore_snapshot(snapshot_store.as_ref());
+            let history =
+                restore_history(snapshot_store.as_ref(), &dump, settings.history_retention());
+            Self {
+                connection_information,
+                settings,
+                dump: Arc::new(Mutex::new(dump)),
+                refresh: SingleFlight::default(),
+                snapshot_store,
+                history: Arc::new(Mutex::new(history)),
+            }
+        }
+    }
+
+    /// dump の取得方法ごとに異なる部分
+    ///
+    /// キャッシュの更新・同時実行の制御・ログ/トレースは
+    /// [`GachaDataRepository`] の blanket impl で共通化している。
+    #[async_trait::async_trait]
+    pub trait GachadataDumper: std::fmt::Debug + Clone + Send + Sync + 'static {
+        fn state(&self) -> &DumpState;
+
+        /// dump を取得し、SQL 全文を返す
+        async fn fetch_gachadata_dump(&self) -> Result<Vec<u8>, DumpError>;
+
+        /// dump 元テーブルのチェックサムを取得する (既定では MySQL プロトコルで直接接続する)
+        async fn probe_source(&self) -> anyhow::Result<BTreeMap<String, u64>> {
+            checksum_tables(&dump_target(&self.state().connection_information)).await
+        }
+    }
+
+    fn dump_target(connection_information: &MySQL) -> DumpTarget<'_> {
+        let MySQL {
+            host,
+            port,
+            user,
+            password,
+            database,
+            tables,
+        } = connection_information;
+
+        DumpTarget {
+            host,
+            port: *port,
+            user,
+            password,
+            database,
+            tables,
+        }
+    }
+
+    /// dump 元テーブルの変更を確認する
+    ///
+    /// 確認は dump を省略するためだけのものなので、失敗した場合は `None` を返して
+    /// 通常どおり dump を取得させる。
+    async fn probe_source_changes<D: GachadataDumper>(dumper: &D) -> Option<ChangeProbe> {
+        let timeout = dumper.state().settings.timeout();
+        match tokio::time::timeout(timeout, dumper.probe_source()).await {
+            Ok(Ok(checksums)) => Some(ChangeProbe {
+                probed_at: SystemTime::now(),
+                checksums,
+            }),
+            Ok(Err(error)) => {
+                tracing::warn!(
+                    error = format!("{error:#}"),
+                    "dump 元テーブルの変更確認に失敗したため、dump を取得します"
+                );
+                None
+            }
+            Err(_) => {
+                tracing::warn!(
+                    ?timeout,
+                    "dump 元テーブルの変更確認がタイムアウトしたため、dump を取得します"
+                );
+                None
+            }
+        }
+    }
+
+    /// 起動時に、保存済みの dump があればキャッシュに読み込む
+    fn restore_snapshot(snapshot_store: Option<&SnapshotStore>) -> GachadataDumpWithTime {
+        let Some(snapshot_store) = snapshot_store else {
+            return GachadataDumpWithTime::default();
+        };
+
+        match snapshot_store.load_latest() {
+            Ok(Some(snapshot)) => {
+                tracing::info!(
+                    dump.content_hash = ?snapshot.content_hash,
+                    dump.age_secs = snapshot.age().map(|age| age.as_secs()),
+                    "保存済みの dump を読み込みました"
+                );
+                GachadataDumpWithTime {
+                    gacha: parse_gacha(&snapshot.dump.0),
+                    ..snapshot
+                }
+            }
+            Ok(None) => GachadataDumpWithTime::default(),
+            Err(error) => {
+                tracing::warn!(
+                    error = format!("{error:#}"),
+                    "保存済みの dump を読み込めないため、dump を取得するまで待ちます"
+                );
+                GachadataDumpWithTime::default()
+            }
+        }
+    }
+
+    /// 起動時に、保存済みの履歴があれば読み込む
+    ///
+    /// 履歴を保存する前のバージョンから更新した場合は、公開中の dump だけを履歴に入れる。
+    fn restore_history(
+        snapshot_store: Option<&SnapshotStore>,
+        current: &GachadataDumpWithTime,
+        retention: Retention,
+    ) -> DumpHistory {
+        let mut history = snapshot_store
+            .map(|snapshot_store| {
+                snapshot_store.load_history().unwrap_or_else(|error| {
+                    tracing::warn!(
+                        error = format!("{error:#}"),
+                        "保存済みの履歴を読み込めないため、履歴なしで起動します"
+                    );
+                    DumpHistory::default()
+                })
+            })
+            .unwrap_or_default();
+        if history.versions().is_empty()
+            && let Some(version) = current_version(current)
+        {
+            history.record(version, retention, SystemTime::now());
+        }
+        history
+    }
+
+    fn current_version(snapshot: &GachadataDumpWithTime) -> Option<DumpVersion> {
+        Some(DumpVersion {
+            content_hash: snapshot.content_hash.clone()?,
+            published_at: snapshot.dump_time?,
+            dump: snapshot.dump.clone(),
+            row_counts: snapshot.row_counts.clone(),
+            changelog: None,
+        })
+    }
+
+    /// 直前に公開したバージョンから `version` への changelog を作る
+    fn changelog_since(previous: &DumpVersion, version: &DumpVersion) -> Changelog {
+        let changes =
+            changelog_between(&previous.dump.0, &version.dump.0).unwrap_or_else(|error| {
+                tracing::warn!(
+                    %error,
+                    dump.content_hash = %version.content_hash,
+                    "dump を解析できないため、changelog を空にします"
+                );
+                Vec::new()
+            });
+        Changelog {
+            previous_version: previous.content_hash.clone(),
+            changes,
+        }
+    }
+
+    /// 公開中の dump を履歴に追加する
+    ///
+    /// 新しいバージョンであれば、景品とイベントを [`lint`] で検査し、見つかった問題をそれぞれ警告する。
+    /// 保留した dump や内容の変わらない dump は検査しないため、同じ警告は繰り返さない。
+    fn record_published_version<D: GachadataDumper>(dumper: &D) {
+        let Ok(snapshot) = cloned_dump(&dumper.state().dump) else {
+            return;
+        };
+        let Some(mut version) = current_version(&snapshot) else {
+            return;
+        };
+        // dump の解析に時間がかかるため、履歴のロックを持たずに changelog を作る
+        let previous = dumper
+            .state()
+            .history
+            .lock()
+            .ok()
+            .and_then(|history| history.versions().first().cloned());
+        if let Some(previous) = previous
+            && previous.content_hash != version.content_hash
+        {
+            version.changelog = Some(changelog_since(&previous, &version));
+        }
+
+        let content_hash = version.content_hash.clone();
+        let recorded = match dumper.state().history.lock() {
+            Ok(mut history) => {
+                let recorded = history.record(
+                    version,
+                    dumper.state().settings.history_retention(),
+                    SystemTime::now(),
+                );
+                if recorded {
+                    tracing::info!(
+                        dump.content_hash = %content_hash,
+                        dump.history_len = history.versions().len(),
+                        "新しいバージョンの dump を公開しました"
+                    );
+                }
+                recorded
+            }
+            Err(_) => false,
+        };
+        if recorded && let Some(gacha) = &snapshot.gacha {
+            for finding in lint(gacha) {
+                tracing::warn!(
+                    dump.content_hash = %content_hash,
+                    lint.rule = ?finding.rule,
+                    lint.event_id = finding.event_id,
+                    lint.prize_ids = ?finding.prize_ids,
+                    "{}",
+                    finding.message
+                );
+            }
+        }
+    }
+
+    /// 公開中の dump をディスクに保存する
+    ///
+    /// 保存は再起動に備えるためだけのものなので、失敗してもキャッシュ済みの dump はそのまま使う。
+    async fn persist_snapshot<D: GachadataDumper>(dumper: &D) {
+        let Some(snapshot_store) = dumper.state().snapshot_store.as_ref() else {
+            return;
+        };
+        // 保存する内容はロックを取ってから読む (SnapshotWriter を参照)
+        let writer = snapshot_store.lock().await;
+        let Ok(snapshot) = cloned_dump(&dumper.state().dump) else {
+            return;
+        };
+        let Ok(history) = dumper.state().history.lock().map(|history| history.clone()) else {
+            return;
+        };
+
+        match tokio::task::spawn_blocking(move || writer.save(&snapshot, &history)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(error)) => tracing::warn!(
+                error = format!("{error:#}"),
+                "dump をディスクに保存できませんでした"
+            ),
+            Err(error) => tracing::warn!(%error, "dump をディスクに保存できませんでした"),
+        }
+    }
+
+    // dumper を skip しないと Debug 経由で MySQL パスワードとキャッシュ済み
+    // dump 全体が span 属性としてトレース基盤へ送られる
+    #[tracing::instrument(
+        skip(dumper),
+        fields(
+            dump.database = %dumper.state().connection_information.database,
+            dump.tables = ?dumper.state().connection_information.tables,
+            dump.timeout_secs = dumper.state().settings.timeout_secs,
+            dump.timed_out = false,
+            dump.unchanged = false,
+            dump.error.kind = tracing::field::Empty,
+        )
+    )]
+    async fn run_gachadata_dump<D: GachadataDumper>(dumper: &D) -> Result<(), DumpError> {
+        let change_probe = probe_source_changes(dumper).await;
+        if let Some(change_probe) = &change_probe
+            && extend_if_unchanged(&dumper.state().dump, change_probe)?
+        {
+            Span::current().record("dump.unchanged", true);
+            tracing::info!(
+                dump.checksums = ?change_probe.checksums,
+                "dump 元テーブルに変更がないため dump を省略しました"
+            );
+            persist_snapshot(dumper).await;
+            return Ok(());
+        }
+        if let Some(change_probe) = &change_probe
+            && is_still_held_back(&dumper.state().dump, change_probe)?
+        {
+            Span::current().record("dump.unchanged", true);
+            tracing::info!(
+                dump.checksums = ?change_probe.checksums,
+                "dump 元テーブルが保留中の dump から変わっていないため dump を省略しました"
+            );
+            return Ok(());
+        }
+
+        let stored = match with_timeout(
+            dumper.state().settings.timeout(),
+            dumper.fetch_gachadata_dump(),
+        )
+        .await
+        {
+            // 検証・正規化・景品の読み取りは dump 全体を走査するため、tokio のワーカーを塞がないよう
+            // blocking スレッドで行う
+            Ok(output) => {
+                let state = dumper.state().clone();
+                tokio::task::spawn_blocking(move || {
+                    store_dump(
+                        &state.dump,
+                        &state.connection_information,
+                        &state.settings,
+                        change_probe,
+                        output,
+                    )
+                })
+                .await
+                .unwrap_or_else(|join_error| Err(DumpError::TaskFailed(join_error)))
+            }
+            Err(error) => Err(error),
+        };
+        match stored {
+            Ok(()) => {}
+            // 保留は更新の失敗ではなく承認待ちなので、公開中の dump に失敗として記録しない
+            Err(error @ (DumpError::HeldBack { .. } | DumpError::StillHeldBack { .. })) => {
+                error.log();
+                return Ok(());
+            }
+            Err(error) => {
+                error.log();
+                record_refresh_failure(&dumper.state().dump, &error);
+                return Err(error);
+            }
+        }
+
+        record_published_version(dumper);
+        persist_snapshot(dumper).await;
+        Ok(())
+    }
+
+    #[async_trait::async_trait]
+    impl<D: GachadataDumper> GachaDataRepository for D {
+        // skip(self): run_gachadata_dump と同じ理由
+        #[tracing::instrument(skip(self))]
+        async fn update_gachadata(&self) -> anyhow::Result<()> {
+            if self.is_gachadata_expired() {
+                self.refresh_gachadata().await?
+            }
+
+            Ok(())
+        }
+
+        // skip(self): run_gachadata_dump と同じ理由
+        #[tracing::instrument(skip(self))]
+        async fn refresh_gachadata(&self) -> anyhow::Result<()> {
+            // 同時に来た更新は 1 回の dump の結果を共有する
+            let this = self.clone();
+            self.state()
+                .refresh
+                .run(async move { run_gachadata_dump(&this).await })
+                .await?;
+
+            Ok(())
+        }
+
+        fn is_gachadata_expired(&self) -> bool {
+            is_dump_older_than(&self.state().dump, self.state().settings.refresh_interval())
+        }
+
+        fn is_gachadata_past_max_age(&self) -> bool {
+            is_dump_older_than(&self.state().dump, self.state().settings.max_age())
+        }
+
+        fn gachadata_dump(&self) -> anyhow::Result<GachadataDumpWithTime> {
+            cloned_dump(&self.state().dump)
+        }
+
+        async fn approve_held_back_gachadata(
+            &self,
+            version: &ContentHash,
+        ) -> anyhow::Result<Option<HeldBackDump>> {
+            let approved = approve_held_back(&self.state().dump, version)?;
+            if approved.is_some() {
+                record_published_version(self);
+                persist_snapshot(self).await;
+            }
+            Ok(approved)
+        }
+
+        fn gachadata_versions(&self) -> anyhow::Result<Vec<DumpVersion>> {
+            self.state()
+                .history
+                .lock()
+                .map(|history| history.versions().to_vec())
+                .map_err(|_| anyhow!("Failed to lock gachadata dump history."))
+        }
+
+        fn gachadata_version(&self, id: &ContentHash) -> anyhow::Result<Option<DumpVersion>> {
+            self.state()
+                .history
+                .lock()
+                .map(|history| history.get(id).cloned())
+                .map_err(|_| anyhow!("Failed to lock gachadata dump history."))
+        }
+    }
+
+    /// `refresh_interval` ごとに dump を更新し続ける
+    ///
+    /// 起動直後にも 1 回 dump するため、最初のリクエストが来る前にキャッシュが温まる。
+    pub async fn refresh_periodically(
+        repository: Arc<dyn GachaDataRepository>,
+        refresh_interval: Duration,
+    ) {
+        let mut interval = tokio::time::interval(refresh_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(error) = repository.refresh_gachadata().await {
+                tracing::warn!(%error, "定期 dump 更新に失敗しました。次回の更新で再試行します");
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct MySQLDumpConnection {
+        pub state: DumpState,
+    }
+
+    impl MySQLDumpConnection {
+        pub fn new(connection_information: MySQL, settings: Dump) -> Self {
+            Self {
+                state: DumpState::new(connection_information, settings),
+            }
+        }
+
+        /// `mariadb-dump` や `mariadb` の、接続先を指定した実行コマンドを組み立てる
+        ///
+        /// パスワードを argv に含めると `/proc/<pid>/cmdline` から誰でも読めるため、
+        /// 所有ユーザーしか読めない環境変数 `MYSQL_PWD` で子プロセスにだけ渡す。
+        fn mariadb_client_command(&self, program: &str) -> Command {
+            let MySQL {
+                host: address,
+                port,
+                user,
+                password,
+                ..
+            } = &self.state.connection_information;
+
+            let mut command = Command::new(program);
+            command
+                .args(vec![
+                    "--host",
+                    address,
+                    "--port",
+                    port.to_string().as_str(),
+                    "--user",
+                    user,
+                ])
+                .env("MYSQL_PWD", password)
+                // タイムアウトで future が drop されたときに子プロセスを kill する
+                .kill_on_drop(true);
+
+            command
+        }
+
+        /// `mariadb-dump` の実行コマンドを組み立てる
+        fn mariadb_dump_command(&self) -> Command {
+            let MySQL {
+                database, tables, ..
+            } = &self.state.connection_information;
+
+            let mut command = self.mariadb_client_command("mariadb-dump");
+            command.arg(database).args(tables);
+            command
+        }
+
+        /// dump 元テーブルの `CHECKSUM TABLE` を `mariadb` で実行するコマンドを組み立てる
+        ///
+        /// 変更の確認にも `mariadb-dump` と同じクライアントと接続設定を使い、
+        /// dump だけが通る環境で別の接続経路を必要としないようにする。
+        fn mariadb_checksum_command(&self) -> Command {
+            let MySQL {
+                database, tables, ..
+            } = &self.state.connection_information;
+
+            let mut command = self.mariadb_client_command("mariadb");
+            command
+                .args(["--batch", "--skip-column-names", "--execute"])
+                .arg(checksum_table_statement(tables))
+                .arg(database);
+            command
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GachadataDumper for MySQLDumpConnection {
+        fn state(&self) -> &DumpState {
+            &self.state
+        }
+
+        async fn fetch_gachadata_dump(&self) -> Result<Vec<u8>, DumpError> {
+            self.mariadb_dump_command()
+                .output()
+                .await
+                .map_err(DumpError::Spawn)
+                .and_then(check_mariadb_dump_output)
+        }
+
+        async fn probe_source(&self) -> anyhow::Result<BTreeMap<String, u64>> {
+            let output = self
+                .mariadb_checksum_command()
+                .output()
+                .await
+                .context("failed to run mariadb")?;
+            ensure!(
+                output.status.success(),
+                "mariadb exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            parse_checksum_output(&self.state.connection_information.tables, &output.stdout)
+        }
+    }
+
+    /// `mariadb-dump` を使わず、MySQL プロトコルで直接 dump を生成するバックエンド
+    #[derive(Debug, Clone)]
+    pub struct NativeDumpConnection {
+        pub state: DumpState,
+    }
+
+    impl NativeDumpConnection {
+        pub fn new(connection_information: MySQL, settings: Dump) -> Self {
+            Self {
+                state: DumpState::new(connection_information, settings),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl GachadataDumper for NativeDumpConnection {
+        fn state(&self) -> &DumpState {
+            &self.state
+        }
+
+        async fn fetch_gachadata_dump(&self) -> Result<Vec<u8>, DumpError> {
+            dump_tables(&dump_target(&self.state.connection_information))
+                .await
+                .map_err(DumpError::Native)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{
+            DumpError, DumpState, GachadataDumper, MySQLDumpConnection, SingleFlight,
+            approve_held_back, check_mariadb_dump_output, record_published_version, store_dump,
+            with_timeout,
+        };
+        use crate::config::{Dump, MySQL};
+        use crate::domain::{
+            ContentHash, GachaDataRepository, GachadataDump, GachadataDumpWithTime,
+        };
+        use crate::dump_history::DumpHistory;
+        use crate::dump_normalization::normalize_dump;
+        use crate::snapshot_store::SnapshotStore;
+        use bytes::Bytes;
+        use std::collections::BTreeMap;
+        use std::ffi::OsStr;
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::{ExitStatus, Output};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Mutex};
+        use std::time::{Duration, Instant, SystemTime};
+        use tokio::process::Command;
+
+        fn mysql(password: &str) -> MySQL {
+            MySQL {
+                host: "db".to_owned(),
+                port: 3306,
+                user: "gachadata".to_owned(),
+                password: password.to_owned(),
+                database: "seichiassist".to_owned(),
+                tables: vec!["gachadata".to_owned(), "gacha_events".to_owned()],
+            }
+        }
+
+        fn dump_settings() -> Dump {
+            envy::from_iter::<_, Dump>(std::iter::empty::<(String, String)>()).unwrap()
+        }
+
+        #[test]
+        fn password_is_passed_via_environment_not_argv() {
+            const SECRET: &str = "s3cr3t-p@ss";
+            let connection = MySQLDumpConnection::new(mysql(SECRET), dump_settings());
+
+            for (command, program) in [
+                (connection.mariadb_dump_command(), "mariadb-dump"),
+                (connection.mariadb_checksum_command(), "mariadb"),
+            ] {
+                let command = command.as_std();
+
+                assert_eq!(command.get_program(), program);
+                for arg in command.get_args() {
+                    let arg = arg.to_string_lossy();
+                    assert!(
+                        !arg.contains(SECRET),
+                        "argv は /proc/<pid>/cmdline から読めるためパスワードを含めない: {arg}"
+                    );
+                }
+                assert!(
+                    command.get_envs().any(
+                        |(key, value)| key == "MYSQL_PWD" && value == Some(OsStr::new(SECRET))
+                    ),
+                    "パスワードは MYSQL_PWD で渡す"
+                );
+            }
+        }
+
+        fn output(exit_code: i32, stdout: &[u8], stderr: &[u8]) -> Output {
+            Output {
+                // wait(2) の status 形式では終了コードは上位バイトに入る
+                status: ExitStatus::from_raw(exit_code << 8),
+                stdout: stdout.to_vec(),
+                stderr: stderr.to_vec(),
+            }
+        }
+
+        #[tokio::test]
+        async fn concurrent_refreshes_share_a_single_dump() {
+            let single_flight = SingleFlight::default();
+            let dump_count = Arc::new(AtomicUsize::new(0));
+
+            let callers = (0..16).map(|_| {
+                let single_flight = single_flight.clone();
+                let dump_count = Arc::clone(&dump_count);
+                tokio::spawn(async move {
+                    single_flight
+                        .run(async move {
+                            dump_count.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            Err(DumpError::EmptyOutput)
+                        })
+                        .await
+                })
+            });
+            let results = futures_util::future::join_all(callers).await;
+
+            assert_eq!(dump_count.load(Ordering::SeqCst), 1);
+            for result in results {
+                assert!(
+                    matches!(
+                        result.unwrap().as_ref().map_err(|error| &**error),
+                        Err(DumpError::EmptyOutput)
+                    ),
+                    "待っていた呼び出しにも同じ失敗が共有される"
+                );
+            }
+
+            // 完了後の呼び出しは新しい dump を開始する
+            single_flight.run(async { Ok(()) }).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn hung_dump_process_is_killed_on_timeout() {
+            let started = Instant::now();
+            let mut command = Command::new("sleep");
+            command.arg("30").kill_on_drop(true);
+
+            let result = with_timeout(Duration::from_millis(100), async {
+                command.output().await.map_err(DumpError::Spawn)
+            })
+            .await;
+
+            assert!(matches!(result, Err(DumpError::Timeout(_))));
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "タイムアウト後は子プロセスの終了を待たない"
+            );
+        }
+
+        #[test]
+        fn non_zero_exit_is_reported_with_stderr() {
+            let error = check_mariadb_dump_output(output(
+                2,
+                b"-- partial",
+                b"mariadb-dump: Got error: 1045: Access denied\n",
+            ))
+            .expect_err("non-zero exit must fail");
+
+            match error {
+                DumpError::NonZeroExit { status, stderr } => {
+                    assert_eq!(status.code(), Some(2));
+                    assert_eq!(stderr, "mariadb-dump: Got error: 1045: Access denied");
+                }
+                other => panic!("unexpected error: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn empty_stdout_is_rejected() {
+            assert!(matches!(
+                check_mariadb_dump_output(output(0, b"", b"")),
+                Err(DumpError::EmptyOutput)
+            ));
+            assert_eq!(
+                check_mariadb_dump_output(output(0, b"-- dump", b"")).unwrap(),
+                b"-- dump"
+            );
+        }
+
+        const VALID_DUMP: &[u8] = b"CREATE TABLE `gachadata` (\n\
+            ) ENGINE=InnoDB;\n\
+            INSERT INTO `gachadata` VALUES (1,0.5),(2,0.25);\n\
+            CREATE TABLE `gacha_events` (\n\
+            ) ENGINE=InnoDB;\n\
+            -- Dump completed on 2024-01-01 12:00:00\n";
+
+        #[test]
+        fn validated_dump_replaces_cache_with_row_counts_and_version() {
+            let dump = Mutex::new(GachadataDumpWithTime::default());
+
+            store_dump(
+                &dump,
+                &mysql("password"),
+                &dump_settings(),
+                None,
+                VALID_DUMP,
+            )
+            .unwrap();
+
+            let dump = dump.lock().unwrap();
+            assert!(
+                dump.dump.0.ends_with(b"\n-- Dump completed\n"),
+                "完了日時は取り除かれる"
+            );
+            assert_eq!(dump.content_hash, Some(ContentHash::of(&dump.dump.0)));
+            assert_eq!(dump.row_counts["gachadata"], 2);
+            assert_eq!(dump.row_counts["gacha_events"], 0);
+        }
+
+        #[test]
+        fn truncated_dump_does_not_replace_cached_dump() {
+            let dump = Mutex::new(GachadataDumpWithTime {
+                dump: GachadataDump(Bytes::from_static(b"-- previous")),
+                ..GachadataDumpWithTime::default()
+            });
+
+            let truncated = &VALID_DUMP[..VALID_DUMP.len() / 2];
+            assert!(matches!(
+                store_dump(&dump, &mysql("password"), &dump_settings(), None, truncated),
+                Err(DumpError::Invalid(_))
+            ));
+            assert_eq!(
+                dump.lock().unwrap().dump.0,
+                Bytes::from_static(b"-- previous")
+            );
+        }
+
+        #[test]
+        fn failed_dump_does_not_replace_cached_dump() {
+            let cached = GachadataDumpWithTime {
+                dump: GachadataDump(Bytes::from_static(b"-- previous")),
+                dump_time: Some(SystemTime::UNIX_EPOCH),
+                ..GachadataDumpWithTime::default()
+            };
+            let dump = Mutex::new(cached);
+
+            assert!(matches!(
+                store_dump(
+                    &dump,
+                    &mysql("password"),
+                    &dump_settings(),
+                    None,
+                    Vec::new()
+                ),
+                Err(DumpError::EmptyOutput)
+            ));
+
+            let dump = dump.lock().unwrap();
+            assert_eq!(dump.dump.0, Bytes::from_static(b"-- previous"));
+            assert_eq!(dump.dump_time, Some(SystemTime::UNIX_EPOCH));
+        }
+
+        #[test]
+        fn shrunk_dump_is_held_back_until_approved() {
+            let dump = Mutex::new(GachadataDumpWithTime::default());
+            store_dump(
+                &dump,
+                &mysql("password"),
+                &dump_settings(),
+                None,
+                VALID_DUMP,
+            )
+            .unwrap();
+
+            let emptied = String::from_utf8_lossy(VALID_DUMP)
+                .replace("INSERT INTO `gachadata` VALUES (1,0.5),(2,0.25);\n", "");
+            let error = store_dump(
+                &dump,
+                &mysql("password"),
+                &dump_settings(),
+                None,
+                emptied.clone(),
+            )
+            .expect_err("行数が半分未満に減った dump は保留する");
+            let DumpError::HeldBack { version, shrinkage } = error else {
+                panic!("unexpected error: {error:?}");
+            };
+            assert_eq!(shrinkage[0].subject, "rows.gachadata");
+            let error = store_dump(
+                &dump,
+                &mysql("password"),
+                &dump_settings(),
+                None,
+                emptied.clone(),
+            )
+            .expect_err("保留中の dump と同じ内容なら保留したまま");
+            assert!(
+                matches!(&error, DumpError::StillHeldBack { version: still } if *still == version),
+                "unexpected error: {error:?}"
+            );
+            {
+                let dump = dump.lock().unwrap();
+                assert_eq!(dump.dump.0, normalize_dump(VALID_DUMP));
+                assert!(dump.held_back.is_some());
+            }
+
+            assert!(
+                approve_held_back(&dump, &ContentHash::of(b"other"))
+                    .unwrap()
+                    .is_none(),
+                "確認したものと異なるバージョンは承認しない"
+            );
+            let approved = approve_held_back(&dump, &version)
+                .unwrap()
+                .expect("保留中の dump がある");
+            assert_eq!(approved.snapshot.row_counts["gachadata"], 0);
+            let dump = dump.lock().unwrap();
+            assert_eq!(dump.dump.0, normalize_dump(emptied.as_bytes()));
+            assert_eq!(dump.content_hash, Some(version));
+            assert!(dump.held_back.is_none());
+        }
+
+        /// チェックサムを差し替えられ、dump の取得回数を数える dumper
+        #[derive(Debug, Clone)]
+        struct ProbedDumper {
+            state: DumpState,
+            checksum: Arc<AtomicUsize>,
+            fetches: Arc<AtomicUsize>,
+        }
+
+        #[async_trait::async_trait]
+        impl GachadataDumper for ProbedDumper {
+            fn state(&self) -> &DumpState {
+                &self.state
+            }
+
+            async fn fetch_gachadata_dump(&self) -> Result<Vec<u8>, DumpError> {
+                self.fetches.fetch_add(1, Ordering::SeqCst);
+                Ok(VALID_DUMP.to_vec())
+            }
+
+            async fn probe_source(&self) -> anyhow::Result<BTreeMap<String, u64>> {
+                let checksum = self.checksum.load(Ordering::SeqCst) as u64;
+                Ok(BTreeMap::from([("gachadata".to_owned(), checksum)]))
+            }
+        }
+
+        #[test]
+        fn published_version_records_changelog_since_previous_version() {
+            let dumper = ProbedDumper {
+                state: DumpState::new(mysql("password"), dump_settings()),
+                checksum: Arc::default(),
+                fetches: Arc::default(),
+            };
+            let publish = |rows: &str| {
+                let dump = format!(
+                    "CREATE TABLE `gachadata` (\n  `id` int(11) NOT NULL,\n  \
+                     `probability` double NOT NULL,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB;\n\
+                     INSERT INTO `gachadata` VALUES {rows};\n\
+                     CREATE TABLE `gacha_events` (\n) ENGINE=InnoDB;\n\
+                     -- Dump completed\n"
+                );
+                store_dump(
+                    &dumper.state.dump,
+                    &dumper.state.connection_information,
+                    &dumper.state.settings,
+                    None,
+                    dump.into_bytes(),
+                )
+                .unwrap();
+                record_published_version(&dumper);
+            };
+
+            publish("(1,0.5),(2,0.25)");
+            publish("(1,0.4),(2,0.25)");
+
+            let versions = dumper.gachadata_versions().unwrap();
+            let changelog = versions[0].changelog.as_ref().unwrap();
+            assert_eq!(changelog.previous_version, versions[1].content_hash);
+            assert_eq!(changelog.changes, ["確率変更: 景品 #1 0.5 → 0.4"]);
+            assert_eq!(
+                versions[1].changelog, None,
+                "最初のバージョンには比較元がない"
+            );
+        }
+
+        #[test]
+        fn saved_snapshot_is_served_right_after_restart() {
+            let dir = tempfile::tempdir().unwrap();
+            let settings = Dump {
+                snapshot_dir: Some(dir.path().to_owned()),
+                ..dump_settings()
+            };
+            let dump = Mutex::new(GachadataDumpWithTime::default());
+            store_dump(&dump, &mysql("password"), &settings, None, VALID_DUMP).unwrap();
+            let saved = dump.into_inner().unwrap();
+            SnapshotStore::new(dir.path().to_owned())
+                .blocking_lock()
+                .save(&saved, &DumpHistory::default())
+                .unwrap();
+
+            let restarted = MySQLDumpConnection::new(mysql("password"), settings);
+
+            let restored = restarted.gachadata_dump().unwrap();
+            assert_eq!(restored.dump.0, saved.dump.0);
+            assert_eq!(restored.content_hash, saved.content_hash);
+            assert_eq!(restored.row_counts, saved.row_counts);
+            assert_eq!(
+                restarted.gachadata_versions().unwrap()[0].content_hash,
+                saved.content_hash.unwrap(),
+                "履歴がなければ公開中の dump から始める"
+            );
+        }
+
+        #[tokio::test]
+        async fn unchanged_source_extends_dump_time_without_dumping() {
+            let dumper = ProbedDumper {
+                state: DumpState::new(mysql("password"), dump_settings()),
+                checksum: Arc::new(AtomicUsize::new(1)),
+                fetches: Arc::default(),
+            };
+
+            dumper.refresh_gachadata().await.unwrap();
+            let first_dump_time = dumper.gachadata_dump().unwrap().dump_time;
+            dumper.refresh_gachadata().await.unwrap();
+
+            let snapshot = dumper.gachadata_dump().unwrap();
+            assert_eq!(dumper.fetches.load(Ordering::SeqCst), 1);
+            assert!(
+                snapshot.dump_time > first_dump_time,
+                "dump_time は延長される"
+            );
+            assert_eq!(snapshot.change_probe.unwrap().checksums["gachadata"], 1);
+
+            dumper.checksum.store(2, Ordering::SeqCst);
+            dumper.refresh_gachadata().await.unwrap();
+            assert_eq!(
+                dumper.fetches.load(Ordering::SeqCst),
+                2,
+                "チェックサムが変わったら dump を取り直す"
+            );
+            assert_eq!(
+                dumper.gachadata_versions().unwrap().len(),
+                1,
+                "同じ内容の dump は履歴に重複して残さない"
+            );
+        }
+
+        #[tokio::test]
+        async fn held_back_dump_is_not_a_failure_and_is_not_dumped_again() {
+            let dumper = ProbedDumper {
+                state: DumpState::new(mysql("password"), dump_settings()),
+                checksum: Arc::new(AtomicUsize::new(1)),
+                fetches: Arc::default(),
+            };
+            let larger = String::from_utf8_lossy(VALID_DUMP).replace(
+                "(1,0.5),(2,0.25)",
+                "(1,0.5),(2,0.25),(3,0.1),(4,0.1),(5,0.05)",
+            );
+            store_dump(
+                &dumper.state.dump,
+                &dumper.state.connection_information,
+                &dumper.state.settings,
+                None,
+                larger,
+            )
+            .unwrap();
+
+            dumper.refresh_gachadata().await.unwrap();
+            dumper.refresh_gachadata().await.unwrap();
+
+            let snapshot = dumper.gachadata_dump().unwrap();
+            assert!(snapshot.held_back.is_some());
+            assert!(
+                snapshot.last_refresh_failure.is_none(),
+                "保留は更新の失敗として扱わない"
+            );
+            assert_eq!(
+                dumper.fetches.load(Ordering::SeqCst),
+                1,
+                "保留中の dump から変わっていなければ dump を取り直さない"
+            );
+        }
+    }
+}
+
+// `GET /` と管理用 API の handler と、`version_api` などの API ごとの handler が使う共通の処理
+//
+// handler の `#[tracing::instrument]` では必ず repository を skip する。Debug 経由で
+// MySQL パスワードとキャッシュ済み dump が span 属性に入るのを防ぐため。
+mod presentation {
+    use crate::config::Gacha;
+    use crate::domain::{ContentHash, GachaData, GachaDataRepository, HeldBackDump};
+    use axum::Json;
+    use axum::extract::{FromRef, Query, Request, State};
+    use axum::http::{HeaderMap, StatusCode, header};
+    use axum::middleware::Next;
+    use axum::response::{ErrorResponse, IntoResponse, Response, Result};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tracing::Instrument;
+
+    /// ルーターの state
+    #[derive(Clone)]
+    pub struct AppState {
+        pub repository: Arc<dyn GachaDataRepository>,
+        pub gacha: Arc<Gacha>,
+    }
+
+    impl FromRef<AppState> for Arc<dyn GachaDataRepository> {
+        fn from_ref(state: &AppState) -> Self {
+            Arc::clone(&state.repository)
+        }
+    }
+
+    impl FromRef<AppState> for Arc<Gacha> {
+        fn from_ref(state: &AppState) -> Self {
+            Arc::clone(&state.gacha)
+        }
+    }
+
+    /// キャッシュ済みの dump をすぐ返せるようにし、古ければバックグラウンドで更新する
+    /// (stale-while-revalidate)
+    ///
+    /// 一度も dump できていない場合だけは返せるものがないため、dump の完了を待つ。
+    pub async fn revalidate(repository: &Arc<dyn GachaDataRepository>) -> anyhow::Result<()> {
+        let never_dumped = repository.gachadata_dump()?.dump_time.is_none();
+        if never_dumped {
+            return repository.update_gachadata().await;
+        }
+
+        if repository.is_gachadata_expired() {
+            let repository = Arc::clone(repository);
+            tokio::spawn(
+                async move {
+                    if let Err(error) = repository.update_gachadata().await {
+                        tracing::warn!(%error, "バックグラウンドでの dump 更新に失敗しました");
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// `If-None-Match` が `etag` に一致するかどうか
+    fn matches_if_none_match(request_headers: &HeaderMap, etag: &str) -> bool {
+        request_headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|candidate| candidate.trim().trim_start_matches("W/"))
+            .any(|candidate| candidate == etag || candidate == "*")
+    }
+
+    #[tracing::instrument(skip(repository, request_headers))]
+    pub async fn get_gachadata_handler(
+        State(repository): State<Arc<dyn GachaDataRepository>>,
+        request_headers: HeaderMap,
+    ) -> Result<impl IntoResponse> {
+        // 更新に失敗しても、以前に取得できた dump があればそれを返す
+        let update_result = revalidate(&repository).await;
+        if let Err(err) = &update_result {
+            tracing::error!("{}", err);
+        }
+
+        match repository.gachadata_dump() {
+            Ok(gachadata_dump) if !gachadata_dump.dump.0.is_empty() => {
+                let mut response = Response::builder()
+                    .status(StatusCode::OK)
+                    .header("Content-Disposition", "attachment; filename=gachadata.sql")
+                    .header("Content-Type", "application/sql")
+                    .header("X-Gachadata-Database", &gachadata_dump.database)
+                    .header("X-Gachadata-Tables", gachadata_dump.tables.join(","));
+                // dump のバージョン (正規化した dump の SHA-256) をそのまま ETag にする
+                let etag = gachadata_dump
+                    .content_hash
+                    .as_ref()
+                    .map(|version| format!("\"{version}\""));
+                if let (Some(version), Some(etag)) = (&gachadata_dump.content_hash, &etag) {
+                    response = response
+                        .header("X-Gachadata-Version", version.to_string())
+                        .header(header::ETAG, etag);
+                }
+                let past_max_age = repository.is_gachadata_past_max_age();
+                // DUMP_MAX_AGE_SECS を超えた dump は黙って返さず、古いことを明示する
+                if past_max_age {
+                    tracing::warn!(
+                        dump_age_secs = gachadata_dump.age().map(|age| age.as_secs()),
+                        "最大許容時間を超えた古い dump を返します"
+                    );
+                    response = response.header("Warning", r#"110 - "Response is Stale""#);
+                }
+                if let Some(failure) = &gachadata_dump.last_refresh_failure {
+                    tracing::warn!(
+                        dump_age_secs = gachadata_dump.age().map(|age| age.as_secs()),
+                        refresh_failure = %failure.reason,
+                        "dump の更新に失敗しているため、最後に取得できた dump を返します"
+                    );
+                    response = response.header("Warning", r#"111 - "Revalidation Failed""#);
+                }
+                if past_max_age || gachadata_dump.last_refresh_failure.is_some() {
+                    response = response.header("X-Gachadata-Stale", "true");
+                }
+                if etag.is_some_and(|etag| matches_if_none_match(&request_headers, &etag)) {
+                    return Ok(response
+                        .status(StatusCode::NOT_MODIFIED)
+                        .body(().into_response())
+                        .unwrap());
+                }
+                Ok(response
+                    .body(gachadata_dump.dump.0.to_owned().into_response())
+                    .unwrap())
+            }
+            Ok(_) if update_result.is_err() => Err(ErrorResponse::from(
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to update gachadata dump. \
+                    Please contact to administrators.",
+                )
+                    .into_response(),
+            )),
+            Ok(_) => Err(ErrorResponse::from(
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "GachadataDump is empty. \
+                    Please contact to administrators.",
+                )
+                    .into_response(),
+            )),
+            Err(err) => {
+                tracing::error!("{}", err);
+                Err(ErrorResponse::from(
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to lock repository mutex.\
+                         Please contact to administrators.",
+                    )
+                        .into_response(),
+                ))
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct HealthResponse {
+        /// `ok`: 最新の更新に成功している / `degraded`: 更新に失敗し古い dump を返している /
+        /// `unavailable`: 一度も dump できていない
+        status: &'static str,
+        /// 公開中の dump のバージョン
+        content_hash: Option<String>,
+        dump_time_unix_secs: Option<u64>,
+        dump_age_secs: Option<u64>,
+        row_counts: BTreeMap<String, u64>,
+        /// 公開中の dump から景品とイベントを読み取れたかどうか
+        gacha_parsed: bool,
+        last_refresh_failure: Option<RefreshFailureResponse>,
+    }
+
+    #[derive(Serialize)]
+    pub struct RefreshFailureResponse {
+        failed_at_unix_secs: u64,
+        reason: String,
+    }
+
+    pub fn unix_secs(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
 
-    #[derive(Clone, Default)]
-    pub struct GachadataDump(pub Bytes);
+    /// dump の更新状況を返す。一度も dump できていなければ 503 を返す
+    #[tracing::instrument(skip(repository))]
+    pub async fn get_health_handler(
+        State(repository): State<Arc<dyn GachaDataRepository>>,
+    ) -> Result<impl IntoResponse> {
+        let gachadata_dump = repository.gachadata_dump().map_err(|err| {
+            tracing::error!("{}", err);
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
 
-    // dump の中身 (SQL 全文) を Debug 出力に含めない
-    impl Debug for GachadataDump {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            f.debug_struct("GachadataDump")
-                .field("len_bytes", &self.0.len())
-                .finish()
+        let (status_code, status) = match (
+            gachadata_dump.dump.0.is_empty(),
+            &gachadata_dump.last_refresh_failure,
+        ) {
+            (true, _) => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+            (false, Some(_)) => (StatusCode::OK, "degraded"),
+            (false, None) => (StatusCode::OK, "ok"),
+        };
+
+        Ok((
+            status_code,
+            Json(HealthResponse {
+                status,
+                content_hash: gachadata_dump.content_hash.clone().map(|version| version.0),
+                dump_time_unix_secs: gachadata_dump.dump_time.map(unix_secs),
+                dump_age_secs: gachadata_dump.age().map(|age| age.as_secs()),
+                row_counts: gachadata_dump.row_counts,
+                gacha_parsed: gachadata_dump.gacha.is_some(),
+                last_refresh_failure: gachadata_dump.last_refresh_failure.map(|failure| {
+                    RefreshFailureResponse {
+                        failed_at_unix_secs: unix_secs(failure.failed_at),
+                        reason: failure.reason,
+                    }
+                }),
+            }),
+        ))
+    }
+
+    pub fn internal_server_error(err: anyhow::Error) -> ErrorResponse {
+        tracing::error!("{}", err);
+        ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    /// 公開中の dump のバージョンと、その dump から読み取った景品とイベント
+    ///
+    /// `/` と同じキャッシュ済みの dump を使い、古ければバックグラウンドで更新する。
+    pub async fn published_gacha(
+        repository: &Arc<dyn GachaDataRepository>,
+    ) -> Result<(ContentHash, Arc<GachaData>)> {
+        if let Err(err) = revalidate(repository).await {
+            tracing::error!("{}", err);
+        }
+        let snapshot = repository.gachadata_dump().map_err(internal_server_error)?;
+        match (snapshot.content_hash, snapshot.gacha) {
+            (Some(version), Some(gacha)) => Ok((version, gacha)),
+            (None, _) => Err(ErrorResponse::from(
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "No gachadata dump is available yet.",
+                )
+                    .into_response(),
+            )),
+            (Some(_), None) => Err(ErrorResponse::from(
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to read prizes and events from the gachadata dump. \
+                    Please contact to administrators.",
+                )
+                    .into_response(),
+            )),
         }
     }
 
-    #[derive(Debug, Clone, Default)]
-    pub struct GachadataDumpWithTime {
-        pub dump: GachadataDump,
-        pub dump_time: Option<SystemTime>,
+    /// dump のバージョンを ETag にした JSON レスポンス。`If-None-Match` が一致すれば 304 を返す
+    pub fn versioned_json(
+        version: &ContentHash,
+        request_headers: &HeaderMap,
+        body: impl Serialize,
+    ) -> Response {
+        let etag = format!("\"{version}\"");
+        let headers = [
+            (header::ETAG, etag.clone()),
+            (
+                header::HeaderName::from_static("x-gachadata-version"),
+                version.to_string(),
+            ),
+        ];
+        if matches_if_none_match(request_headers, &etag) {
+            return (StatusCode::NOT_MODIFIED, headers).into_response();
+        }
+        (headers, Json(body)).into_response()
     }
 
-    #[async_trait::async_trait]
-    pub trait GachaDataRepository: Debug + Sync + Send + 'static {
-        async fn update_gachadata(&self) -> anyhow::Result<()>;
+    /// `Authorization: Bearer <ADMIN_TOKEN>` が付いたリクエストだけを通す
+    pub async fn require_admin_token(
+        State(admin_token): State<Arc<str>>,
+        request: Request,
+        next: Next,
+    ) -> Response {
+        let authorized = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), admin_token.as_bytes()));
+
+        if authorized {
+            next.run(request).await
+        } else {
+            StatusCode::UNAUTHORIZED.into_response()
+        }
     }
-}
 
-mod infra_repository_impls {
-    use crate::config::MySQL;
-    use crate::domain::{GachaDataRepository, GachadataDump, GachadataDumpWithTime};
-    use anyhow::anyhow;
-    use bytes::Bytes;
-    use std::ops::Sub;
-    use std::process::Command;
-    use std::sync::{Arc, Mutex};
-    use std::time::{Duration, SystemTime};
+    /// 比較にかかる時間からトークンを推測されないよう、一致するかどうかに関わらず全体を比較する
+    fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+    }
 
-    #[derive(Debug, Clone)]
-    pub struct MySQLDumpConnection {
-        pub connection_information: MySQL,
-        pub dump: Arc<Mutex<GachadataDumpWithTime>>,
+    #[derive(Serialize)]
+    pub struct HeldBackDumpResponse {
+        /// 承認するときに指定するバージョン
+        content_hash: Option<String>,
+        dump_time_unix_secs: Option<u64>,
+        size_bytes: usize,
+        row_counts: BTreeMap<String, u64>,
+        shrinkage: Vec<ShrinkageResponse>,
     }
 
-    impl MySQLDumpConnection {
-        // self を skip しないと Debug 経由で MySQL パスワードとキャッシュ済み
-        // dump 全体が span 属性としてトレース基盤へ送られる
-        #[tracing::instrument(skip(self))]
-        pub async fn run_gachadata_dump(&self) -> anyhow::Result<()> {
-            let MySQL {
-                host: address,
-                port,
-                user,
-                password,
-            } = &self.connection_information;
+    #[derive(Serialize)]
+    pub struct ShrinkageResponse {
+        subject: String,
+        previous: u64,
+        current: u64,
+    }
 
-            let output = Command::new("mariadb-dump")
-                .args(vec![
-                    "--host",
-                    address,
-                    "--port",
-                    port.to_string().as_str(),
-                    "--user",
-                    user,
-                    format!("-p{}", password).as_str(),
-                    "seichiassist",
-                    "gachadata",
-                    "gacha_events",
-                ])
-                .output()?;
+    impl From<HeldBackDump> for HeldBackDumpResponse {
+        fn from(held_back: HeldBackDump) -> Self {
+            HeldBackDumpResponse {
+                content_hash: held_back.snapshot.content_hash.map(|version| version.0),
+                dump_time_unix_secs: held_back.snapshot.dump_time.map(unix_secs),
+                size_bytes: held_back.snapshot.dump.0.len(),
+                row_counts: held_back.snapshot.row_counts,
+                shrinkage: held_back
+                    .shrinkage
+                    .into_iter()
+                    .map(|shrinkage| ShrinkageResponse {
+                        subject: shrinkage.subject,
+                        previous: shrinkage.previous,
+                        current: shrinkage.current,
+                    })
+                    .collect(),
+            }
+        }
+    }
 
-            if let Ok(mut dump) = self.dump.lock() {
-                *dump = GachadataDumpWithTime {
-                    dump: GachadataDump(Bytes::from(output.stdout)),
-                    dump_time: Some(SystemTime::now()),
-                }
-            } else {
-                return Err(anyhow!("Failed to lock gachadata dump."));
+    fn no_held_back_dump() -> ErrorResponse {
+        ErrorResponse::from(
+            (
+                StatusCode::NOT_FOUND,
+                "No dump with the given version is held back.",
+            )
+                .into_response(),
+        )
+    }
+
+    /// 公開を保留している dump と、保留した理由を返す
+    #[tracing::instrument(skip(repository))]
+    pub async fn get_held_back_dump_handler(
+        State(repository): State<Arc<dyn GachaDataRepository>>,
+    ) -> Result<Json<HeldBackDumpResponse>> {
+        let gachadata_dump = repository.gachadata_dump().map_err(|err| {
+            tracing::error!("{}", err);
+            ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+
+        gachadata_dump
+            .held_back
+            .map(|held_back| Json(HeldBackDumpResponse::from(*held_back)))
+            .ok_or_else(no_held_back_dump)
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct ApproveQuery {
+        /// 承認する dump のバージョン (`GET /admin/held-back-dump` の `content_hash`)
+        version: String,
+    }
+
+    /// 公開を保留している dump を承認して公開する
+    #[tracing::instrument(skip(repository))]
+    pub async fn approve_held_back_dump_handler(
+        State(repository): State<Arc<dyn GachaDataRepository>>,
+        Query(query): Query<ApproveQuery>,
+    ) -> Result<Json<HeldBackDumpResponse>> {
+        let approved = repository
+            .approve_held_back_gachadata(&ContentHash(query.version))
+            .await
+            .map_err(|err| {
+                tracing::error!("{}", err);
+                ErrorResponse::from(StatusCode::INTERNAL_SERVER_ERROR)
+            })?;
+
+        approved
+            .map(|held_back| Json(HeldBackDumpResponse::from(held_back)))
+            .ok_or_else(no_held_back_dump)
+    }
+
+    /// handler のテストで共通に使う repository と設定
+    #[cfg(test)]
+    pub mod test_support {
+        use crate::config::Gacha;
+        use crate::domain::{
+            ContentHash, DumpVersion, GachaData, GachaDataRepository, GachaEvent, GachaPrize,
+            GachadataDump, GachadataDumpWithTime, HeldBackDump, ItemStack,
+        };
+        use axum::response::Response;
+        use bytes::Bytes;
+        use std::collections::BTreeMap;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::SystemTime;
+        use tokio::sync::Notify;
+
+        /// dump の更新が `release` されるまで終わらない repository
+        #[derive(Debug, Default)]
+        pub struct SlowRepository {
+            pub snapshot: Option<GachadataDumpWithTime>,
+            pub versions: Vec<DumpVersion>,
+            pub past_max_age: bool,
+            pub updates: AtomicUsize,
+            pub release: Notify,
+        }
+
+        #[async_trait::async_trait]
+        impl GachaDataRepository for SlowRepository {
+            async fn update_gachadata(&self) -> anyhow::Result<()> {
+                self.updates.fetch_add(1, Ordering::SeqCst);
+                self.release.notified().await;
+                Ok(())
             }
 
-            Ok(())
+            async fn refresh_gachadata(&self) -> anyhow::Result<()> {
+                self.update_gachadata().await
+            }
+
+            fn is_gachadata_expired(&self) -> bool {
+                true
+            }
+
+            fn is_gachadata_past_max_age(&self) -> bool {
+                self.past_max_age
+            }
+
+            fn gachadata_dump(&self) -> anyhow::Result<GachadataDumpWithTime> {
+                Ok(self.snapshot.clone().unwrap_or_default())
+            }
+
+            async fn approve_held_back_gachadata(
+                &self,
+                _version: &ContentHash,
+            ) -> anyhow::Result<Option<HeldBackDump>> {
+                Ok(None)
+            }
+
+            fn gachadata_versions(&self) -> anyhow::Result<Vec<DumpVersion>> {
+                Ok(self.versions.clone())
+            }
+
+            fn gachadata_version(&self, id: &ContentHash) -> anyhow::Result<Option<DumpVersion>> {
+                Ok(self
+                    .versions
+                    .iter()
+                    .find(|version| &version.content_hash == id)
+                    .cloned())
+            }
+        }
+
+        /// 通常のガチャの景品 2 つと、イベント「正月」の景品 2 つを持つ repository
+        pub fn gacha_repository() -> Arc<dyn GachaDataRepository> {
+            let prize = |id, probability, event_id| GachaPrize {
+                id,
+                probability,
+                itemstack: Some(Bytes::from_static(b"\xac\xed")),
+                item: Some(ItemStack {
+                    material: "DIAMOND".to_owned(),
+                    amount: 1,
+                    damage: 0,
+                    display_name: Some("§bすごいダイヤ".to_owned()),
+                    lore: vec![format!("§7景品 #{id}")],
+                    enchantments: BTreeMap::from([("DURABILITY".to_owned(), 3)]),
+                    custom_model_data: None,
+                }),
+                event_id,
+            };
+            Arc::new(SlowRepository {
+                snapshot: Some(GachadataDumpWithTime {
+                    dump: GachadataDump(Bytes::from_static(b"-- current")),
+                    content_hash: Some(ContentHash::of(b"-- current")),
+                    dump_time: Some(SystemTime::now()),
+                    gacha: Some(Arc::new(GachaData {
+                        prizes: vec![
+                            prize(1, 0.1, None),
+                            prize(2, 0.01, Some(1)),
+                            prize(3, 0.5, None),
+                            prize(4, 0.2, Some(1)),
+                        ],
+                        events: vec![GachaEvent {
+                            id: 1,
+                            name: "正月".to_owned(),
+                            start_time: jiff::civil::date(2024, 1, 1).at(0, 0, 0, 0),
+                            end_time: jiff::civil::date(2024, 1, 7).at(23, 59, 59, 0),
+                        }],
+                    })),
+                    ..GachadataDumpWithTime::default()
+                }),
+                ..SlowRepository::default()
+            })
+        }
+
+        pub async fn json_body(response: Response) -> serde_json::Value {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice(&body).unwrap()
+        }
+
+        pub fn gacha_settings() -> Arc<Gacha> {
+            Arc::new(envy::from_iter::<_, Gacha>(std::iter::empty::<(String, String)>()).unwrap())
         }
     }
 
-    #[async_trait::async_trait]
-    impl GachaDataRepository for MySQLDumpConnection {
-        // skip(self): run_gachadata_dump と同じ理由
-        #[tracing::instrument(skip(self))]
-        async fn update_gachadata(&self) -> anyhow::Result<()> {
-            let is_after_more_than_quarter_hour = match self.dump.lock() {
-                Ok(dump) => {
-                    let quarter_hour = Duration::from_secs(900);
-                    let dump_time = dump.dump_time;
+    #[cfg(test)]
+    mod tests {
+        use super::test_support::SlowRepository;
+        use super::{get_gachadata_handler, get_health_handler, require_admin_token, revalidate};
+        use crate::domain::{
+            ContentHash, GachaDataRepository, GachadataDump, GachadataDumpWithTime, RefreshFailure,
+        };
+        use axum::extract::State;
+        use axum::http::{HeaderMap, StatusCode};
+        use axum::response::IntoResponse;
+        use bytes::Bytes;
+        use std::collections::BTreeMap;
+        use std::sync::Arc;
+        use std::sync::atomic::Ordering;
+        use std::time::{Duration, SystemTime};
 
-                    let quarter_hour_from_now = SystemTime::now().sub(quarter_hour);
+        #[tokio::test]
+        async fn stale_dump_is_served_without_waiting_for_refresh() {
+            let repository = Arc::new(SlowRepository {
+                snapshot: Some(GachadataDumpWithTime {
+                    dump: GachadataDump(Bytes::from_static(b"-- stale")),
+                    dump_time: Some(SystemTime::UNIX_EPOCH),
+                    ..GachadataDumpWithTime::default()
+                }),
+                ..SlowRepository::default()
+            });
+            let dyn_repository: Arc<dyn GachaDataRepository> = repository.clone();
 
-                    match dump_time {
-                        Some(dump_time) => quarter_hour_from_now > dump_time,
-                        None => true, // dump_timeがNoneになるのは起動して一度も取得されていないときのみ
-                    }
+            tokio::time::timeout(Duration::from_secs(1), revalidate(&dyn_repository))
+                .await
+                .expect("古い dump があれば更新を待たずに返す")
+                .unwrap();
+
+            // spawn された更新が始まるのを待つ
+            tokio::time::timeout(Duration::from_secs(1), async {
+                while repository.updates.load(Ordering::SeqCst) == 0 {
+                    tokio::task::yield_now().await;
                 }
-                _ => false,
+            })
+            .await
+            .expect("バックグラウンドで更新が始まる");
+            repository.release.notify_one();
+        }
+
+        #[tokio::test]
+        async fn dump_past_max_age_is_marked_as_stale() {
+            let repository = Arc::new(SlowRepository {
+                snapshot: Some(GachadataDumpWithTime {
+                    dump: GachadataDump(Bytes::from_static(b"-- old")),
+                    content_hash: Some(ContentHash::of(b"-- old")),
+                    dump_time: Some(SystemTime::UNIX_EPOCH),
+                    database: "seichiassist".to_owned(),
+                    tables: vec!["gachadata".to_owned(), "gacha_events".to_owned()],
+                    row_counts: BTreeMap::new(),
+                    change_probe: None,
+                    last_refresh_failure: None,
+                    held_back: None,
+                    gacha: None,
+                }),
+                past_max_age: true,
+                ..SlowRepository::default()
+            });
+            repository.release.notify_one();
+
+            let response = get_gachadata_handler(
+                State(repository as Arc<dyn GachaDataRepository>),
+                HeaderMap::new(),
+            )
+            .await
+            .unwrap()
+            .into_response();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["X-Gachadata-Stale"], "true");
+            assert_eq!(
+                response.headers()["X-Gachadata-Tables"],
+                "gachadata,gacha_events"
+            );
+            assert!(response.headers().contains_key("Warning"));
+        }
+
+        #[tokio::test]
+        async fn unchanged_version_is_answered_with_not_modified() {
+            let version = ContentHash::of(b"-- current");
+            let repository: Arc<dyn GachaDataRepository> = Arc::new(SlowRepository {
+                snapshot: Some(GachadataDumpWithTime {
+                    dump: GachadataDump(Bytes::from_static(b"-- current")),
+                    content_hash: Some(version.clone()),
+                    dump_time: Some(SystemTime::now()),
+                    ..GachadataDumpWithTime::default()
+                }),
+                ..SlowRepository::default()
+            });
+            let request = |if_none_match: String| {
+                let mut headers = HeaderMap::new();
+                headers.insert("If-None-Match", if_none_match.parse().unwrap());
+                get_gachadata_handler(State(Arc::clone(&repository)), headers)
             };
 
-            // 最終dumpの取得から15分以上経過していればGachaDumpを更新する
-            if is_after_more_than_quarter_hour {
-                self.run_gachadata_dump().await?
+            let response = request(format!("\"{version}\""))
+                .await
+                .unwrap()
+                .into_response();
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(response.headers()["ETag"], format!("\"{version}\""));
+
+            let response = request("\"outdated\"".to_owned())
+                .await
+                .unwrap()
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["X-Gachadata-Version"], version.0);
+        }
+
+        fn failed_refresh_snapshot() -> GachadataDumpWithTime {
+            GachadataDumpWithTime {
+                dump: GachadataDump(Bytes::from_static(b"-- last known good")),
+                dump_time: Some(SystemTime::now()),
+                last_refresh_failure: Some(RefreshFailure {
+                    failed_at: SystemTime::now(),
+                    reason: "mariadb-dump exited with exit status: 2".to_owned(),
+                }),
+                ..GachadataDumpWithTime::default()
             }
+        }
 
-            Ok(())
+        #[tokio::test]
+        async fn last_known_good_dump_is_served_after_refresh_failure() {
+            let repository = Arc::new(SlowRepository {
+                snapshot: Some(failed_refresh_snapshot()),
+                ..SlowRepository::default()
+            });
+            repository.release.notify_one();
+
+            let response = get_gachadata_handler(
+                State(repository as Arc<dyn GachaDataRepository>),
+                HeaderMap::new(),
+            )
+            .await
+            .unwrap()
+            .into_response();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["X-Gachadata-Stale"], "true");
+            assert_eq!(
+                response.headers()["Warning"],
+                r#"111 - "Revalidation Failed""#
+            );
         }
-    }
-}
 
-mod presentation {
-    use crate::domain::GachaDataRepository;
-    use crate::infra_repository_impls::MySQLDumpConnection;
-    use axum::extract::State;
-    use axum::http::StatusCode;
-    use axum::response::{ErrorResponse, IntoResponse, Response, Result};
+        #[tokio::test]
+        async fn health_reports_refresh_failure() {
+            let degraded: Arc<dyn GachaDataRepository> = Arc::new(SlowRepository {
+                snapshot: Some(failed_refresh_snapshot()),
+                ..SlowRepository::default()
+            });
+            let response = get_health_handler(State(degraded))
+                .await
+                .unwrap()
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["status"], "degraded");
+            assert_eq!(
+                json["last_refresh_failure"]["reason"],
+                "mariadb-dump exited with exit status: 2"
+            );
 
-    // skip(repository): Debug 経由で MySQL パスワードとキャッシュ済み dump が
-    // span 属性に入るのを防ぐ
-    #[tracing::instrument(skip(repository))]
-    pub async fn get_gachadata_handler(
-        State(repository): State<MySQLDumpConnection>,
-    ) -> Result<impl IntoResponse> {
-        match repository.update_gachadata().await {
-            Ok(_) => match repository.dump.lock() {
-                Ok(gachadata_dump) if !gachadata_dump.dump.0.is_empty() => Ok(Response::builder()
-                    .status(StatusCode::OK)
-                    .header("Content-Disposition", "attachment; filename=gachadata.sql")
-                    .header("Content-Type", "application/sql")
-                    .body(gachadata_dump.dump.0.to_owned().into_response())
-                    .unwrap()),
-                Ok(_) => Err(ErrorResponse::from(
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "GachadataDump is empty. \
-                        Please contact to administrators.",
-                    )
-                        .into_response(),
-                )),
-                Err(err) => {
-                    tracing::error!("{}", err);
-                    Err(ErrorResponse::from(
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            "Failed to lock repository mutex.\
-                             Please contact to administrators.",
-                        )
-                            .into_response(),
-                    ))
+            let unavailable: Arc<dyn GachaDataRepository> = Arc::new(SlowRepository::default());
+            let response = get_health_handler(State(unavailable))
+                .await
+                .unwrap()
+                .into_response();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        #[tokio::test]
+        async fn admin_routes_require_bearer_token() {
+            use axum::body::Body;
+            use axum::http::Request;
+            use tower::ServiceExt;
+
+            let router = axum::Router::new()
+                .route("/admin", axum::routing::get(|| async { "ok" }))
+                .route_layer(axum::middleware::from_fn_with_state(
+                    std::sync::Arc::<str>::from("admin-token"),
+                    require_admin_token,
+                ));
+            let status = |authorization: Option<&'static str>| {
+                let router = router.clone();
+                async move {
+                    let mut request = Request::get("/admin");
+                    if let Some(authorization) = authorization {
+                        request = request.header("Authorization", authorization);
+                    }
+                    router
+                        .oneshot(request.body(Body::empty()).unwrap())
+                        .await
+                        .unwrap()
+                        .status()
                 }
-            },
-            Err(err) => {
-                tracing::error!("{}", err);
-                Err(ErrorResponse::from(
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Failed to update gachadata dump. \
-                        Please contact to administrators.",
-                    )
-                        .into_response(),
-                ))
-            }
+            };
+
+            assert_eq!(status(None).await, StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                status(Some("Bearer wrong-token")).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(status(Some("Bearer admin-token")).await, StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn first_request_waits_for_initial_dump() {
+            let repository = Arc::new(SlowRepository::default());
+            let dyn_repository: Arc<dyn GachaDataRepository> = repository.clone();
+
+            let pending = tokio::spawn(async move { revalidate(&dyn_repository).await });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(
+                !pending.is_finished(),
+                "一度も dump していなければ完了を待つ"
+            );
+
+            repository.release.notify_one();
+            pending.await.unwrap().unwrap();
         }
     }
 }
 
 mod config {
+    use crate::dump_history::Retention;
+    use crate::give_command::MinecraftVersion;
     use serde::Deserialize;
+    use std::path::PathBuf;
+    use std::time::Duration;
 
     #[derive(Debug, Deserialize)]
     pub struct HttpPort {
@@ -187,6 +2441,12 @@ mod config {
         pub port: u16,
         pub user: String,
         pub password: String,
+        /// dump 対象のデータベース名
+        #[serde(default = "MySQL::default_database")]
+        pub database: String,
+        /// dump 対象のテーブル (`MYSQL_TABLES=gachadata,gacha_events` のようにカンマ区切り)
+        #[serde(default = "MySQL::default_tables")]
+        pub tables: Vec<String>,
     }
 
     // パスワードを Debug 出力に含めない (span/ログへ誤って載せた場合の多層防御)
@@ -197,21 +2457,316 @@ mod config {
                 .field("port", &self.port)
                 .field("user", &self.user)
                 .field("password", &"<redacted>")
+                .field("database", &self.database)
+                .field("tables", &self.tables)
+                .finish()
+        }
+    }
+
+    impl MySQL {
+        fn default_database() -> String {
+            "seichiassist".to_owned()
+        }
+
+        fn default_tables() -> Vec<String> {
+            vec!["gachadata".to_owned(), "gacha_events".to_owned()]
+        }
+
+        /// データベース名・テーブル名は `mariadb-dump` の引数や SQL にそのまま埋め込むため、
+        /// クォート不要な識別子 (英数字・`_`・`$`) だけを受け付ける
+        fn validate(&self) -> anyhow::Result<()> {
+            fn is_plain_identifier(name: &str) -> bool {
+                !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+            }
+
+            anyhow::ensure!(
+                is_plain_identifier(&self.database),
+                "MYSQL_DATABASE is not a valid database name: {:?}",
+                self.database
+            );
+            anyhow::ensure!(!self.tables.is_empty(), "MYSQL_TABLES must not be empty");
+            for (index, table) in self.tables.iter().enumerate() {
+                anyhow::ensure!(
+                    is_plain_identifier(table),
+                    "MYSQL_TABLES contains an invalid table name: {table:?}"
+                );
+                anyhow::ensure!(
+                    !self.tables[..index].contains(table),
+                    "MYSQL_TABLES contains a duplicate table: {table:?}"
+                );
+            }
+            Ok(())
+        }
+    }
+
+    /// dump の取得方法
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    pub enum DumpBackend {
+        /// `mariadb-dump` コマンドを子プロセスとして実行する
+        #[default]
+        MariadbDump,
+        /// MySQL プロトコルで直接接続し、プロセス内で dump を生成する
+        Native,
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    pub struct Dump {
+        #[serde(default)]
+        pub backend: DumpBackend,
+        /// これを超えても dump が終わらなければ中断する (秒)
+        #[serde(default = "Dump::default_timeout_secs")]
+        pub timeout_secs: u64,
+        /// 最終 dump の取得からこの時間が経過したら dump を更新する (秒)
+        #[serde(default = "Dump::default_refresh_interval_secs")]
+        pub refresh_interval_secs: u64,
+        /// 最終 dump の取得からこの時間を超えた dump は古いものとして印をつけて返す (秒)
+        #[serde(default = "Dump::default_max_age_secs")]
+        pub max_age_secs: u64,
+        /// 公開中の dump からテーブルの行数または dump のサイズがこの割合を超えて減った場合、
+        /// 新しい dump の公開を保留する (0.0 〜 1.0。1.0 で無効)
+        #[serde(default = "Dump::default_max_shrink_ratio")]
+        pub max_shrink_ratio: f64,
+        /// 公開した dump を保存するディレクトリ。未設定なら保存しない
+        pub snapshot_dir: Option<PathBuf>,
+        /// 履歴に残す dump の最大数
+        #[serde(default = "Dump::default_history_max_versions")]
+        pub history_max_versions: usize,
+        /// 公開からこの時間を過ぎた dump は履歴から消す (秒)
+        #[serde(default = "Dump::default_history_max_age_secs")]
+        pub history_max_age_secs: u64,
+    }
+
+    impl Dump {
+        fn default_timeout_secs() -> u64 {
+            300
+        }
+
+        fn default_refresh_interval_secs() -> u64 {
+            900
+        }
+
+        fn default_max_age_secs() -> u64 {
+            3600
+        }
+
+        fn default_max_shrink_ratio() -> f64 {
+            0.5
+        }
+
+        fn default_history_max_versions() -> usize {
+            30
+        }
+
+        fn default_history_max_age_secs() -> u64 {
+            90 * 24 * 60 * 60
+        }
+
+        pub fn history_retention(&self) -> Retention {
+            Retention {
+                max_versions: self.history_max_versions,
+                max_age: Duration::from_secs(self.history_max_age_secs),
+            }
+        }
+
+        pub fn timeout(&self) -> Duration {
+            Duration::from_secs(self.timeout_secs)
+        }
+
+        pub fn refresh_interval(&self) -> Duration {
+            Duration::from_secs(self.refresh_interval_secs)
+        }
+
+        pub fn max_age(&self) -> Duration {
+            Duration::from_secs(self.max_age_secs)
+        }
+
+        fn validate(&self) -> anyhow::Result<()> {
+            anyhow::ensure!(
+                self.timeout_secs > 0,
+                "DUMP_TIMEOUT_SECS must be greater than 0"
+            );
+            anyhow::ensure!(
+                self.refresh_interval_secs > 0,
+                "DUMP_REFRESH_INTERVAL_SECS must be greater than 0"
+            );
+            anyhow::ensure!(
+                self.max_age_secs >= self.refresh_interval_secs,
+                "DUMP_MAX_AGE_SECS ({}) must not be shorter than DUMP_REFRESH_INTERVAL_SECS ({})",
+                self.max_age_secs,
+                self.refresh_interval_secs
+            );
+            anyhow::ensure!(
+                self.history_max_versions > 0,
+                "DUMP_HISTORY_MAX_VERSIONS must be greater than 0"
+            );
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&self.max_shrink_ratio),
+                "DUMP_MAX_SHRINK_RATIO must be between 0.0 and 1.0: {}",
+                self.max_shrink_ratio
+            );
+            Ok(())
+        }
+    }
+
+    #[derive(Default, Deserialize)]
+    pub struct Admin {
+        /// 管理用 API (`/admin/...`) の Bearer トークン。未設定なら管理用 API を公開しない
+        pub token: Option<String>,
+    }
+
+    // トークンを Debug 出力に含めない
+    impl std::fmt::Debug for Admin {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Admin")
+                .field("token", &self.token.as_ref().map(|_| "<redacted>"))
                 .finish()
         }
     }
 
+    impl Admin {
+        fn validate(&self) -> anyhow::Result<()> {
+            anyhow::ensure!(
+                self.token.as_ref().is_none_or(|token| !token.is_empty()),
+                "ADMIN_TOKEN must not be empty"
+            );
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Clone, Deserialize)]
+    pub struct Gacha {
+        /// `gacha_events` の日時 (`DATETIME`) を記録しているタイムゾーンの UTC からのオフセット (時間)
+        #[serde(default = "Gacha::default_event_utc_offset_hours")]
+        pub event_utc_offset_hours: i8,
+        /// 景品の `give` コマンドと SNBT を出力する Minecraft のバージョン
+        /// (リクエストの `?minecraft_version=` がなければこれを使う)
+        #[serde(default = "Gacha::default_minecraft_version")]
+        pub minecraft_version: MinecraftVersion,
+    }
+
+    impl Gacha {
+        fn default_event_utc_offset_hours() -> i8 {
+            // SeichiAssist は日本時間で記録している
+            9
+        }
+
+        fn default_minecraft_version() -> MinecraftVersion {
+            // SeichiAssist のサーバーのバージョン
+            "1.18.2".parse().unwrap()
+        }
+
+        pub fn event_time_zone(&self) -> jiff::tz::TimeZone {
+            jiff::tz::Offset::constant(self.event_utc_offset_hours).to_time_zone()
+        }
+
+        fn validate(&self) -> anyhow::Result<()> {
+            anyhow::ensure!(
+                (-25..=25).contains(&self.event_utc_offset_hours),
+                "GACHA_EVENT_UTC_OFFSET_HOURS must be between -25 and 25: {}",
+                self.event_utc_offset_hours
+            );
+            Ok(())
+        }
+    }
+
     pub struct Config {
         pub http_port: HttpPort,
         pub mysql: MySQL,
+        pub dump: Dump,
+        pub admin: Admin,
+        pub gacha: Gacha,
     }
 
     impl Config {
         pub async fn from_environment() -> anyhow::Result<Self> {
             let http_port = envy::prefixed("HTTP_").from_env::<HttpPort>()?;
             let mysql = envy::prefixed("MYSQL_").from_env::<MySQL>()?;
+            mysql.validate()?;
+            let dump = envy::prefixed("DUMP_").from_env::<Dump>()?;
+            dump.validate()?;
+            let admin = envy::prefixed("ADMIN_").from_env::<Admin>()?;
+            admin.validate()?;
+            let gacha = envy::prefixed("GACHA_").from_env::<Gacha>()?;
+            gacha.validate()?;
+
+            Ok(Config {
+                http_port,
+                mysql,
+                dump,
+                admin,
+                gacha,
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Dump, MySQL};
+
+        fn mysql_from(vars: &[(&str, &str)]) -> anyhow::Result<MySQL> {
+            let required = [
+                ("HOST", "db"),
+                ("PORT", "3306"),
+                ("USER", "user"),
+                ("PASSWORD", "password"),
+            ];
+            let mysql = envy::from_iter::<_, MySQL>(
+                required
+                    .iter()
+                    .chain(vars)
+                    .map(|(key, value)| (key.to_string(), value.to_string())),
+            )?;
+            mysql.validate()?;
+            Ok(mysql)
+        }
+
+        #[test]
+        fn database_and_tables_default_to_seichiassist_gacha_tables() {
+            let mysql = mysql_from(&[]).unwrap();
+            assert_eq!(mysql.database, "seichiassist");
+            assert_eq!(mysql.tables, ["gachadata", "gacha_events"]);
+        }
+
+        #[test]
+        fn tables_are_read_as_comma_separated_list() {
+            let mysql = mysql_from(&[
+                ("DATABASE", "seichiassist_staging"),
+                ("TABLES", "gachadata,gacha_events,seasonal_gachadata"),
+            ])
+            .unwrap();
+            assert_eq!(mysql.database, "seichiassist_staging");
+            assert_eq!(
+                mysql.tables,
+                ["gachadata", "gacha_events", "seasonal_gachadata"]
+            );
+        }
+
+        #[test]
+        fn invalid_table_set_is_rejected_at_startup() {
+            assert!(
+                mysql_from(&[("TABLES", "gachadata,--where=1")]).is_err(),
+                "mariadb-dump のオプションとして解釈されうる名前は拒否する"
+            );
+            assert!(mysql_from(&[("TABLES", "gachadata,gachadata")]).is_err());
+            assert!(mysql_from(&[("DATABASE", "seichi assist")]).is_err());
+        }
 
-            Ok(Config { http_port, mysql })
+        #[test]
+        fn zero_dump_timeout_is_rejected_at_startup() {
+            let dump_from = |timeout_secs: &str| {
+                envy::from_iter::<_, Dump>([("TIMEOUT_SECS".to_owned(), timeout_secs.to_owned())])
+                    .unwrap()
+                    .validate()
+            };
+            assert!(dump_from("300").is_ok());
+            assert!(
+                dump_from("0").is_err(),
+                "すべての dump がすぐにタイムアウトし、一度も dump できなくなる"
+            );
         }
     }
 }
@@ -219,15 +2774,33 @@ mod config {
 #[tokio::main]
 async fn main() {
     use crate::{
-        config::Config, infra_repository_impls::MySQLDumpConnection,
-        presentation::get_gachadata_handler,
+        analysis_api::{get_lint_handler, simulate_handler},
+        config::{Config, DumpBackend},
+        domain::GachaDataRepository,
+        gacha_api::{
+            get_event_handler, get_events_handler, get_events_schema_handler, get_prizes_handler,
+            get_prizes_schema_handler,
+        },
+        give_api::{get_datapack_handler, get_give_function_handler, get_prize_give_handler},
+        infra_repository_impls::{MySQLDumpConnection, NativeDumpConnection, refresh_periodically},
+        presentation::{
+            AppState, approve_held_back_dump_handler, get_gachadata_handler, get_health_handler,
+            get_held_back_dump_handler, require_admin_token,
+        },
+        version_api::{
+            get_changelog_handler, get_version_diff_handler, get_version_dump_handler,
+            get_versions_handler,
+        },
+    };
+    use axum::{
+        Router, middleware,
+        routing::{get, post},
     };
-    use axum::{Router, routing::get};
     use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
     use opentelemetry::trace::TracerProvider as _;
     use pyroscope::backend::{BackendConfig, PprofConfig, pprof_backend};
     use pyroscope::pyroscope::PyroscopeAgentBuilder;
-    use std::sync::{Arc, Mutex};
+    use std::sync::Arc;
     use tokio::net::TcpListener;
     use tower_http::catch_panic::CatchPanicLayer;
     use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
@@ -235,6 +2808,7 @@ async fn main() {
     // OTel トレーシング (OTLP http/protobuf)。
     // OTEL_EXPORTER_OTLP_ENDPOINT 未設定または OTEL_SDK_DISABLED=true なら無効
     let tracer_provider = telemetry::init_tracer_provider();
+    let meter_provider = telemetry::init_meter_provider();
 
     // stdout ログ: 本番は 1 行 JSON (trace_id 注入付き)、ローカル (ENV_NAME=local) は
     // 人間向けフォーマット。LOG_FORMAT=json|pretty で明示上書き可
@@ -301,14 +2875,77 @@ async fn main() {
         .await
         .expect("Failed to load config from environment variables.");
 
-    let mysql_dump_connection = MySQLDumpConnection {
-        connection_information: config.mysql,
-        dump: Arc::new(Mutex::default()),
+    tracing::info!(
+        backend = ?config.dump.backend,
+        database = %config.mysql.database,
+        tables = ?config.mysql.tables,
+        "dump target configured"
+    );
+    let repository: Arc<dyn GachaDataRepository> = match config.dump.backend {
+        DumpBackend::MariadbDump => {
+            Arc::new(MySQLDumpConnection::new(config.mysql, config.dump.clone()))
+        }
+        DumpBackend::Native => {
+            Arc::new(NativeDumpConnection::new(config.mysql, config.dump.clone()))
+        }
     };
 
-    let router = Router::new()
+    // リクエストを待たずに dump を定期更新する
+    let background_refresh = tokio::spawn(refresh_periodically(
+        Arc::clone(&repository),
+        config.dump.refresh_interval(),
+    ));
+
+    let mut router = Router::new()
         .route("/", get(get_gachadata_handler))
-        .with_state(mysql_dump_connection)
+        .route("/health", get(get_health_handler))
+        .route("/versions", get(get_versions_handler))
+        .route(
+            "/versions/{id}/gachadata.sql",
+            get(get_version_dump_handler),
+        )
+        .route("/versions/{from}/diff/{to}", get(get_version_diff_handler))
+        .route("/changelog", get(get_changelog_handler))
+        .route("/api/v1/prizes", get(get_prizes_handler))
+        .route("/api/v1/prizes/{id}/give", get(get_prize_give_handler))
+        .route(
+            "/api/v1/prizes/give.mcfunction",
+            get(get_give_function_handler),
+        )
+        .route("/api/v1/prizes/datapack.zip", get(get_datapack_handler))
+        .route(
+            "/api/v1/schemas/prizes.json",
+            get(get_prizes_schema_handler),
+        )
+        .route("/api/v1/events", get(get_events_handler))
+        .route("/api/v1/events/{name}", get(get_event_handler))
+        .route(
+            "/api/v1/schemas/events.json",
+            get(get_events_schema_handler),
+        )
+        .route("/api/v1/lint", get(get_lint_handler))
+        .route("/api/v1/simulate", post(simulate_handler));
+    // ADMIN_TOKEN が設定されているときだけ管理用 API を公開する
+    if let Some(admin_token) = config.admin.token {
+        router = router.nest(
+            "/admin",
+            Router::new()
+                .route("/held-back-dump", get(get_held_back_dump_handler))
+                .route(
+                    "/held-back-dump/approve",
+                    post(approve_held_back_dump_handler),
+                )
+                .route_layer(middleware::from_fn_with_state(
+                    Arc::<str>::from(admin_token),
+                    require_admin_token,
+                )),
+        );
+    }
+    let router = router
+        .with_state(AppState {
+            repository,
+            gacha: Arc::new(config.gacha),
+        })
         // handler 内 panic で 500 を返し、コネクションを維持する
         // (panic 自体は panic_hook が panic=true 付きでログに残す)
         .layer(CatchPanicLayer::new())
@@ -349,6 +2986,8 @@ async fn main() {
         .await
         .unwrap();
 
+    background_refresh.abort();
+
     // 終了前に未送信のプロファイル・スパンを flush する
     if let Some(agent) = pyroscope_agent {
         match agent.stop() {
@@ -359,4 +2998,7 @@ async fn main() {
     if let Some(provider) = tracer_provider {
         let _ = provider.shutdown();
     }
+    if let Some(provider) = meter_provider {
+        let _ = provider.shutdown();
+    }
 }
@@ -0,0 +1,590 @@
+use crate::domain::{
+    ChangeProbe, Changelog, ContentHash, DumpVersion, GachadataDump, GachadataDumpWithTime,
+};
+use crate::dump_history::DumpHistory;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// 公開した dump をディスクに保存し、再起動後も同じ dump をすぐに返せるようにする
+///
+/// dump は `<バージョン>.sql`、公開中の dump のメタデータは `<バージョン>.json`、
+/// 履歴は `history.json` として保存する。
+/// いずれも一時ファイルに書いてから rename するため、途中で落ちても書きかけの
+/// ファイルは読まれない。メタデータは dump の後に書くので、メタデータがあれば
+/// 対応する dump も書き終わっている。
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+    /// 保存を 1 つずつ行うためのロック ([`SnapshotWriter`])
+    writer: Arc<Mutex<()>>,
+}
+
+/// 保存のロックを取った [`SnapshotStore`]
+///
+/// 定期更新と管理者の承認による保存が同時に走ると、互いの一時ファイルを上書きしたり、
+/// 相手が書いたばかりの dump を不要なものとして削除したりするため、保存は 1 つずつ行う。
+/// 保存する内容はロックを取ってから読むこと。先に読んだ古い内容で、後から保存された
+/// 新しい内容を上書きしないようにするため。
+#[derive(Debug)]
+pub struct SnapshotWriter {
+    store: SnapshotStore,
+    _guard: OwnedMutexGuard<()>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotMetadata {
+    content_hash: String,
+    dump_time_unix_millis: u64,
+    database: String,
+    tables: Vec<String>,
+    row_counts: BTreeMap<String, u64>,
+    change_probe: Option<ChangeProbeMetadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryEntry {
+    content_hash: String,
+    published_at_unix_millis: u64,
+    row_counts: BTreeMap<String, u64>,
+    // changelog を保存するようになる前の history.json も読めるようにする
+    #[serde(default)]
+    changelog: Option<ChangelogEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChangelogEntry {
+    previous_version: String,
+    changes: Vec<String>,
+}
+
+const HISTORY_FILE_NAME: &str = "history.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChangeProbeMetadata {
+    probed_at_unix_millis: u64,
+    checksums: BTreeMap<String, u64>,
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn from_unix_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+impl SnapshotStore {
+    pub fn new(dir: PathBuf) -> Self {
+        SnapshotStore {
+            dir,
+            writer: Arc::default(),
+        }
+    }
+
+    /// 他の保存が終わるのを待ってから保存のロックを取る
+    pub async fn lock(&self) -> SnapshotWriter {
+        SnapshotWriter {
+            store: self.clone(),
+            _guard: Arc::clone(&self.writer).lock_owned().await,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn blocking_lock(&self) -> SnapshotWriter {
+        SnapshotWriter {
+            store: self.clone(),
+            _guard: Arc::clone(&self.writer).blocking_lock_owned(),
+        }
+    }
+
+    fn dump_path(&self, content_hash: &str) -> PathBuf {
+        self.dir.join(format!("{content_hash}.sql"))
+    }
+
+    fn metadata_path(&self, content_hash: &str) -> PathBuf {
+        self.dir.join(format!("{content_hash}.json"))
+    }
+
+    fn write_snapshot(
+        &self,
+        snapshot: &GachadataDumpWithTime,
+        history: &DumpHistory,
+    ) -> anyhow::Result<()> {
+        let (Some(content_hash), Some(dump_time)) = (&snapshot.content_hash, snapshot.dump_time)
+        else {
+            anyhow::bail!("snapshot has not been dumped yet");
+        };
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+
+        // 同じバージョンの dump は中身も同じなので、メタデータ (dump_time など) だけ書き直す
+        self.write_dump_if_absent(content_hash, &snapshot.dump.0)?;
+        for version in history.versions() {
+            self.write_dump_if_absent(&version.content_hash, &version.dump.0)?;
+        }
+        let metadata = SnapshotMetadata {
+            content_hash: content_hash.0.clone(),
+            dump_time_unix_millis: unix_millis(dump_time),
+            database: snapshot.database.clone(),
+            tables: snapshot.tables.clone(),
+            row_counts: snapshot.row_counts.clone(),
+            change_probe: snapshot
+                .change_probe
+                .as_ref()
+                .map(|change_probe| ChangeProbeMetadata {
+                    probed_at_unix_millis: unix_millis(change_probe.probed_at),
+                    checksums: change_probe.checksums.clone(),
+                }),
+        };
+        write_atomically(
+            &self.metadata_path(&content_hash.0),
+            &serde_json::to_vec_pretty(&metadata)?,
+        )?;
+        let history_entries: Vec<_> = history
+            .versions()
+            .iter()
+            .map(|version| HistoryEntry {
+                content_hash: version.content_hash.0.clone(),
+                published_at_unix_millis: unix_millis(version.published_at),
+                row_counts: version.row_counts.clone(),
+                changelog: version.changelog.as_ref().map(|changelog| ChangelogEntry {
+                    previous_version: changelog.previous_version.0.clone(),
+                    changes: changelog.changes.clone(),
+                }),
+            })
+            .collect();
+        write_atomically(
+            &self.dir.join(HISTORY_FILE_NAME),
+            &serde_json::to_vec_pretty(&history_entries)?,
+        )?;
+
+        self.remove_unreferenced(content_hash, history)
+    }
+
+    fn write_dump_if_absent(&self, content_hash: &ContentHash, dump: &[u8]) -> anyhow::Result<()> {
+        let dump_path = self.dump_path(&content_hash.0);
+        if !dump_path.exists() {
+            write_atomically(&dump_path, dump)?;
+        }
+        Ok(())
+    }
+
+    /// 保存済みの履歴を読み込む
+    ///
+    /// dump が見つからないか中身がバージョンと一致しないものは読み飛ばす。
+    pub fn load_history(&self) -> anyhow::Result<DumpHistory> {
+        let history_path = self.dir.join(HISTORY_FILE_NAME);
+        let entries: Vec<HistoryEntry> = match fs::read(&history_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("failed to parse {}", history_path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read {}", history_path.display()));
+            }
+        };
+
+        let mut versions = Vec::new();
+        for entry in entries {
+            let dump_path = self.dump_path(&entry.content_hash);
+            let dump = match fs::read(&dump_path) {
+                Ok(dump) if ContentHash::of(&dump).0 == entry.content_hash => dump,
+                _ => {
+                    tracing::warn!(
+                        path = %dump_path.display(),
+                        "履歴の dump が見つからないか壊れているため無視します"
+                    );
+                    continue;
+                }
+            };
+            versions.push(DumpVersion {
+                content_hash: ContentHash(entry.content_hash),
+                published_at: from_unix_millis(entry.published_at_unix_millis),
+                dump: GachadataDump(dump.into()),
+                row_counts: entry.row_counts,
+                changelog: entry.changelog.map(|changelog| Changelog {
+                    previous_version: ContentHash(changelog.previous_version),
+                    changes: changelog.changes,
+                }),
+            });
+        }
+        Ok(DumpHistory::from_versions(versions))
+    }
+
+    /// 保存済みの dump のうち最も新しいものを読み込む
+    ///
+    /// dump が見つからないか、中身がメタデータのバージョンと一致しないもの (ディスクの破損など) は
+    /// 読み飛ばし、より古い dump を探す。
+    pub fn load_latest(&self) -> anyhow::Result<Option<GachadataDumpWithTime>> {
+        let mut metadata = Vec::new();
+        for path in self
+            .files_with_extension("json")?
+            .into_iter()
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| name != HISTORY_FILE_NAME)
+            })
+        {
+            match read_metadata(&path) {
+                Ok(entry) => metadata.push(entry),
+                Err(error) => tracing::warn!(
+                    path = %path.display(),
+                    error = format!("{error:#}"),
+                    "保存済み dump のメタデータを読めないため無視します"
+                ),
+            }
+        }
+        metadata.sort_by_key(|metadata| std::cmp::Reverse(metadata.dump_time_unix_millis));
+
+        for metadata in metadata {
+            let dump_path = self.dump_path(&metadata.content_hash);
+            let dump = match fs::read(&dump_path) {
+                Ok(dump) => dump,
+                Err(error) => {
+                    tracing::warn!(
+                        path = %dump_path.display(),
+                        %error,
+                        "保存済み dump を読めないため無視します"
+                    );
+                    continue;
+                }
+            };
+            let content_hash = ContentHash::of(&dump);
+            if content_hash.0 != metadata.content_hash {
+                tracing::warn!(
+                    path = %dump_path.display(),
+                    "保存済み dump の中身がバージョンと一致しないため無視します"
+                );
+                continue;
+            }
+
+            return Ok(Some(GachadataDumpWithTime {
+                dump: GachadataDump(dump.into()),
+                content_hash: Some(content_hash),
+                dump_time: Some(from_unix_millis(metadata.dump_time_unix_millis)),
+                database: metadata.database,
+                tables: metadata.tables,
+                row_counts: metadata.row_counts,
+                change_probe: metadata.change_probe.map(|change_probe| ChangeProbe {
+                    probed_at: from_unix_millis(change_probe.probed_at_unix_millis),
+                    checksums: change_probe.checksums,
+                }),
+                last_refresh_failure: None,
+                held_back: None,
+                gacha: None,
+            }));
+        }
+        Ok(None)
+    }
+
+    fn files_with_extension(&self, extension: &str) -> anyhow::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read {}", self.dir.display()));
+            }
+        };
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == extension) {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+
+    fn remove_unreferenced(
+        &self,
+        current: &ContentHash,
+        history: &DumpHistory,
+    ) -> anyhow::Result<()> {
+        let is_current = |stem: &str| stem == current.0;
+        let is_in_history = |stem: &str| {
+            history
+                .versions()
+                .iter()
+                .any(|version| version.content_hash.0 == stem)
+        };
+
+        let unreferenced_metadata = self
+            .files_with_extension("json")?
+            .into_iter()
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| name != HISTORY_FILE_NAME)
+            })
+            .filter(|path| {
+                !path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(is_current)
+            });
+        let unreferenced_dumps = self
+            .files_with_extension("sql")?
+            .into_iter()
+            .filter(|path| {
+                !path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .is_some_and(|stem| is_current(stem) || is_in_history(stem))
+            });
+        for path in unreferenced_metadata.chain(unreferenced_dumps) {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl SnapshotWriter {
+    /// 公開中の dump `snapshot` と履歴 `history` を保存し、どちらにも含まれない
+    /// 保存済み dump を削除する
+    pub fn save(
+        &self,
+        snapshot: &GachadataDumpWithTime,
+        history: &DumpHistory,
+    ) -> anyhow::Result<()> {
+        self.store.write_snapshot(snapshot, history)
+    }
+}
+
+fn read_metadata(path: &Path) -> anyhow::Result<SnapshotMetadata> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// 一時ファイル (`<ファイル名>.tmp`) に書いて fsync してから rename する
+///
+/// `<バージョン>.sql` と `<バージョン>.json` が同じ一時ファイルにならないよう、拡張子は置き換えずに足す。
+fn write_atomically(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    let mut file = File::create(&temporary)
+        .with_context(|| format!("failed to create {}", temporary.display()))?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+        .with_context(|| format!("failed to rename {}", temporary.display()))?;
+    // rename 自体を永続化する
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotStore;
+    use crate::domain::{
+        ChangeProbe, Changelog, ContentHash, DumpVersion, GachadataDump, GachadataDumpWithTime,
+    };
+    use crate::dump_history::{DumpHistory, Retention};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn snapshot(dump: &'static [u8], dump_time_secs: u64) -> GachadataDumpWithTime {
+        let dump_time = UNIX_EPOCH + Duration::from_secs(dump_time_secs);
+        GachadataDumpWithTime {
+            dump: GachadataDump(dump.into()),
+            content_hash: Some(ContentHash::of(dump)),
+            dump_time: Some(dump_time),
+            database: "seichiassist".to_owned(),
+            tables: vec!["gachadata".to_owned()],
+            row_counts: BTreeMap::from([("gachadata".to_owned(), 2)]),
+            change_probe: Some(ChangeProbe {
+                probed_at: dump_time,
+                checksums: BTreeMap::from([("gachadata".to_owned(), 42)]),
+            }),
+            ..GachadataDumpWithTime::default()
+        }
+    }
+
+    #[test]
+    fn saved_snapshot_is_restored_with_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().to_owned());
+
+        store
+            .blocking_lock()
+            .save(&snapshot(b"-- first", 1_000), &DumpHistory::default())
+            .unwrap();
+        store
+            .blocking_lock()
+            .save(&snapshot(b"-- second", 2_000), &DumpHistory::default())
+            .unwrap();
+
+        let restored = store.load_latest().unwrap().expect("保存した dump がある");
+        assert_eq!(&restored.dump.0[..], b"-- second");
+        assert_eq!(restored.content_hash, Some(ContentHash::of(b"-- second")));
+        assert_eq!(
+            restored.dump_time,
+            Some(UNIX_EPOCH + Duration::from_secs(2_000))
+        );
+        assert_eq!(restored.row_counts["gachadata"], 2);
+        assert_eq!(restored.change_probe.unwrap().checksums["gachadata"], 42);
+        assert_eq!(
+            std::fs::read_dir(dir.path()).unwrap().count(),
+            3,
+            "履歴にない古い dump は削除される"
+        );
+    }
+
+    fn history_of(snapshots: &[&GachadataDumpWithTime]) -> DumpHistory {
+        let mut history = DumpHistory::default();
+        let retention = Retention {
+            max_versions: 10,
+            max_age: Duration::MAX,
+        };
+        for snapshot in snapshots {
+            let changelog = history.versions().first().map(|previous| Changelog {
+                previous_version: previous.content_hash.clone(),
+                changes: vec!["確率変更: 景品 #1 0.01 → 0.02".to_owned()],
+            });
+            history.record(
+                DumpVersion {
+                    content_hash: snapshot.content_hash.clone().unwrap(),
+                    published_at: snapshot.dump_time.unwrap(),
+                    dump: snapshot.dump.clone(),
+                    row_counts: snapshot.row_counts.clone(),
+                    changelog,
+                },
+                retention,
+                snapshot.dump_time.unwrap(),
+            );
+        }
+        history
+    }
+
+    #[test]
+    fn history_is_restored_with_dumps() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().to_owned());
+        let first = snapshot(b"-- first", 1_000);
+        let second = snapshot(b"-- second", 2_000);
+
+        store
+            .blocking_lock()
+            .save(&first, &history_of(&[&first]))
+            .unwrap();
+        store
+            .blocking_lock()
+            .save(&second, &history_of(&[&first, &second]))
+            .unwrap();
+
+        let history = store.load_history().unwrap();
+        let restored: Vec<_> = history
+            .versions()
+            .iter()
+            .map(|version| &version.dump.0[..])
+            .collect();
+        assert_eq!(restored, [&b"-- second"[..], &b"-- first"[..]]);
+        assert_eq!(
+            history.versions()[1].published_at,
+            UNIX_EPOCH + Duration::from_secs(1_000)
+        );
+        assert_eq!(
+            history.versions()[0].changelog,
+            Some(Changelog {
+                previous_version: first.content_hash.clone().unwrap(),
+                changes: vec!["確率変更: 景品 #1 0.01 → 0.02".to_owned()],
+            })
+        );
+        assert_eq!(history.versions()[1].changelog, None);
+    }
+
+    #[test]
+    fn concurrent_saves_are_serialized() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().to_owned());
+        let first = snapshot(b"-- first", 1_000);
+        let second = snapshot(b"-- second", 2_000);
+        let history = history_of(&[&first, &second]);
+
+        std::thread::scope(|scope| {
+            for saved in [&first, &second] {
+                let (store, history) = (&store, &history);
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        store.blocking_lock().save(saved, history).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert!(store.load_latest().unwrap().is_some());
+        assert_eq!(store.load_history().unwrap().versions().len(), 2);
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name.to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert_eq!(leftovers, Vec::<std::ffi::OsString>::new());
+    }
+
+    #[test]
+    fn corrupted_or_missing_snapshots_are_not_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().join("not-yet-created"));
+        assert!(store.load_latest().unwrap().is_none());
+
+        let store = SnapshotStore::new(dir.path().to_owned());
+        let saved = snapshot(b"-- saved", 1_000);
+        store
+            .blocking_lock()
+            .save(&saved, &DumpHistory::default())
+            .unwrap();
+        std::fs::write(
+            dir.path()
+                .join(format!("{}.sql", saved.content_hash.unwrap())),
+            b"-- corrupted",
+        )
+        .unwrap();
+        // 書きかけの一時ファイルは読まれない
+        std::fs::write(dir.path().join("partial.tmp"), b"{").unwrap();
+
+        assert!(store.load_latest().unwrap().is_none());
+    }
+
+    #[test]
+    fn older_snapshot_is_restored_when_newer_dump_is_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().to_owned());
+        let older = snapshot(b"-- older", 1_000);
+        let newer = snapshot(b"-- newer", 2_000);
+        // 古い dump は履歴に残し、公開中でなくなると消えるメタデータは書き戻す
+        store
+            .blocking_lock()
+            .save(&older, &history_of(&[&older]))
+            .unwrap();
+        let older_metadata = dir
+            .path()
+            .join(format!("{}.json", older.content_hash.clone().unwrap()));
+        let metadata = std::fs::read(&older_metadata).unwrap();
+        store
+            .blocking_lock()
+            .save(&newer, &history_of(&[&older, &newer]))
+            .unwrap();
+        std::fs::write(&older_metadata, metadata).unwrap();
+        std::fs::remove_file(
+            dir.path()
+                .join(format!("{}.sql", newer.content_hash.unwrap())),
+        )
+        .unwrap();
+
+        let restored = store.load_latest().unwrap().expect("古い dump は読める");
+        assert_eq!(&restored.dump.0[..], b"-- older");
+    }
+}
@@ -0,0 +1,471 @@
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// dump の `INSERT` 文に書かれた値
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SqlValue {
+    Null,
+    /// 数値。精度を落とさないよう dump に書かれた表記のまま持つ
+    Number(String),
+    /// 文字列・BLOB。エスケープを解除したバイト列
+    String(Vec<u8>),
+}
+
+/// JSON では数値は数値、文字列は文字列として表す。UTF-8 でない BLOB は `0x` 付きの 16 進表記にする
+impl Serialize for SqlValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SqlValue::Null => serializer.serialize_none(),
+            SqlValue::Number(number) => {
+                if let Ok(integer) = number.parse::<i64>() {
+                    serializer.serialize_i64(integer)
+                } else if let Ok(float) = number.parse::<f64>() {
+                    serializer.serialize_f64(float)
+                } else {
+                    serializer.serialize_str(number)
+                }
+            }
+            SqlValue::String(bytes) => match std::str::from_utf8(bytes) {
+                Ok(string) => serializer.serialize_str(string),
+                Err(_) => serializer.serialize_str(&hex(bytes)),
+            },
+        }
+    }
+}
+
+/// SQL に近い表記 (`NULL`・`0.5`・`'text'`)
+impl std::fmt::Display for SqlValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SqlValue::Null => f.write_str("NULL"),
+            SqlValue::Number(number) => f.write_str(number),
+            SqlValue::String(bytes) => match std::str::from_utf8(bytes) {
+                Ok(string) => write!(f, "'{}'", string.replace('\'', "''")),
+                Err(_) => f.write_str(&hex(bytes)),
+            },
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(2 + bytes.len() * 2);
+    hex.push_str("0x");
+    for byte in bytes {
+        hex.push_str(&format!("{byte:02X}"));
+    }
+    hex
+}
+
+/// dump から読み取ったテーブル
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    /// `CREATE TABLE` に書かれた順の列名
+    pub columns: Vec<String>,
+    /// 主キーの列名
+    pub primary_key: Vec<String>,
+    /// 各行の値 (`columns` と同じ順)
+    pub rows: Vec<Vec<SqlValue>>,
+    /// 各行が dump のどこに書かれていたか (`rows` と同じ順)
+    pub row_locations: Vec<Location>,
+}
+
+/// dump 中の位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Location {
+    /// 1 始まりの行番号
+    pub line: usize,
+    /// 1 始まりの列番号 (バイト単位)
+    pub column: usize,
+}
+
+/// dump を読めなかった位置と理由
+#[derive(Debug, Clone, thiserror::Error, PartialEq, Eq)]
+#[error("line {line}, column {column}: {reason}")]
+pub struct SqlDumpParseError {
+    /// 1 始まりの行番号
+    pub line: usize,
+    /// 1 始まりの列番号 (バイト単位)
+    pub column: usize,
+    pub reason: String,
+}
+
+/// dump の `CREATE TABLE` と `INSERT` 文を読み、テーブルごとの列と行を返す
+///
+/// `mariadb-dump` (と [`crate::native_dump`]) の出力形式、つまり `CREATE TABLE` の各列と
+/// extended `INSERT` 文がそれぞれ行頭から書かれている形式を前提とする。
+pub fn parse_dump(dump: &[u8]) -> Result<BTreeMap<String, Table>, SqlDumpParseError> {
+    let mut tables = BTreeMap::<String, Table>::new();
+    let mut creating: Option<String> = None;
+    for (index, line) in dump.split(|&byte| byte == b'\n').enumerate() {
+        let line_number = index + 1;
+        if let Some(table) = &creating {
+            let table = tables.entry(table.clone()).or_default();
+            let definition = line.trim_ascii_start();
+            if line.starts_with(b")") {
+                creating = None;
+            } else if let Some((column, _)) = parse_identifier(definition) {
+                table.columns.push(column);
+            } else if let Some(rest) = definition.strip_prefix(b"PRIMARY KEY (") {
+                table.primary_key = parse_identifier_list(rest);
+            }
+        } else if let Some(rest) = line.strip_prefix(b"CREATE TABLE ") {
+            let (table, _) = parse_identifier(rest).ok_or_else(|| SqlDumpParseError {
+                line: line_number,
+                column: "CREATE TABLE ".len() + 1,
+                reason: "expected a backquoted table name".to_owned(),
+            })?;
+            tables.insert(table.clone(), Table::default());
+            creating = Some(table);
+        } else if let Some(rest) = line.strip_prefix(b"INSERT INTO ") {
+            let prefix_len = "INSERT INTO ".len();
+            let (table_name, values) = parse_identifier(rest).ok_or_else(|| SqlDumpParseError {
+                line: line_number,
+                column: prefix_len + 1,
+                reason: "expected a backquoted table name".to_owned(),
+            })?;
+            let table = tables
+                .get_mut(&table_name)
+                .ok_or_else(|| SqlDumpParseError {
+                    line: line_number,
+                    column: prefix_len + 1,
+                    reason: format!("INSERT into `{table_name}` before its CREATE TABLE"),
+                })?;
+            let mut parser = ValuesParser {
+                line,
+                position: line.len() - values.len(),
+                line_number,
+            };
+            for row in parser.parse_rows()? {
+                if row.values.len() != table.columns.len() {
+                    return Err(SqlDumpParseError {
+                        line: line_number,
+                        column: row.start + 1,
+                        reason: format!(
+                            "row has {} values but `{table_name}` has {} columns",
+                            row.values.len(),
+                            table.columns.len()
+                        ),
+                    });
+                }
+                table.rows.push(row.values);
+                table.row_locations.push(Location {
+                    line: line_number,
+                    column: row.start + 1,
+                });
+            }
+        }
+    }
+    Ok(tables)
+}
+
+/// 行頭の (バッククォートされた) 識別子を読み、識別子と残りのバイト列を返す
+pub fn parse_identifier(input: &[u8]) -> Option<(String, &[u8])> {
+    let rest = input.strip_prefix(b"`")?;
+    let mut identifier = Vec::new();
+    let mut index = 0;
+    while index < rest.len() {
+        if rest[index] == b'`' {
+            // `` はバッククォート自体のエスケープ
+            if rest.get(index + 1) == Some(&b'`') {
+                identifier.push(b'`');
+                index += 2;
+                continue;
+            }
+            let identifier = String::from_utf8_lossy(&identifier).into_owned();
+            return Some((identifier, &rest[index + 1..]));
+        }
+        identifier.push(rest[index]);
+        index += 1;
+    }
+    None
+}
+
+/// `` `a`,`b`) `` のような識別子の並びを読む
+fn parse_identifier_list(mut input: &[u8]) -> Vec<String> {
+    let mut identifiers = Vec::new();
+    while let Some((identifier, rest)) = parse_identifier(input) {
+        identifiers.push(identifier);
+        input = rest.strip_prefix(b",").unwrap_or(rest);
+    }
+    identifiers
+}
+
+struct ParsedRow {
+    /// 行の `(` の位置 (行頭からのバイト数)
+    start: usize,
+    values: Vec<SqlValue>,
+}
+
+/// ` VALUES (...),(...);` を読む
+struct ValuesParser<'a> {
+    line: &'a [u8],
+    position: usize,
+    line_number: usize,
+}
+
+impl ValuesParser<'_> {
+    fn error(&self, reason: impl Into<String>) -> SqlDumpParseError {
+        SqlDumpParseError {
+            line: self.line_number,
+            column: self.position + 1,
+            reason: reason.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.line.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, token: &[u8]) -> Result<(), SqlDumpParseError> {
+        if self.line[self.position..].starts_with(token) {
+            self.position += token.len();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", String::from_utf8_lossy(token))))
+        }
+    }
+
+    fn parse_rows(&mut self) -> Result<Vec<ParsedRow>, SqlDumpParseError> {
+        self.skip_whitespace();
+        self.expect(b"VALUES")?;
+        let mut rows = Vec::new();
+        loop {
+            self.skip_whitespace();
+            rows.push(self.parse_row()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b';') => {
+                    self.position += 1;
+                    self.skip_whitespace();
+                    if self.position != self.line.len() {
+                        return Err(self.error("unexpected input after `;`"));
+                    }
+                    return Ok(rows);
+                }
+                Some(_) => return Err(self.error("expected `,` or `;` after a row")),
+                None => return Err(self.error("statement is truncated (missing `;`)")),
+            }
+        }
+    }
+
+    fn parse_row(&mut self) -> Result<ParsedRow, SqlDumpParseError> {
+        let start = self.position;
+        self.expect(b"(")?;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace();
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b')') => {
+                    self.position += 1;
+                    return Ok(ParsedRow { start, values });
+                }
+                Some(_) => return Err(self.error("expected `,` or `)` after a value")),
+                None => return Err(self.error("row is truncated (missing `)`)")),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<SqlValue, SqlDumpParseError> {
+        let rest = &self.line[self.position..];
+        if rest.starts_with(b"NULL") {
+            self.position += 4;
+            return Ok(SqlValue::Null);
+        }
+        // MySQL の mysqldump は BLOB に `_binary` を付ける
+        if rest.starts_with(b"_binary ") {
+            self.position += "_binary ".len();
+            return self.parse_string();
+        }
+        if rest.starts_with(b"0x") {
+            return self.parse_hex();
+        }
+        match self.peek() {
+            Some(b'\'') => self.parse_string(),
+            Some(byte) if byte == b'-' || byte == b'+' || byte == b'.' || byte.is_ascii_digit() => {
+                let start = self.position;
+                while self.peek().is_some_and(|byte| {
+                    byte.is_ascii_digit() || matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E')
+                }) {
+                    self.position += 1;
+                }
+                let number = String::from_utf8_lossy(&self.line[start..self.position]).into_owned();
+                if number.parse::<f64>().is_err() {
+                    self.position = start;
+                    return Err(self.error(format!("invalid number `{number}`")));
+                }
+                Ok(SqlValue::Number(number))
+            }
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("row is truncated (missing value)")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<SqlValue, SqlDumpParseError> {
+        let start = self.position;
+        self.expect(b"'")?;
+        let mut bytes = Vec::new();
+        while let Some(byte) = self.peek() {
+            self.position += 1;
+            match byte {
+                b'\\' => {
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("string is truncated (ends with a backslash)"))?;
+                    self.position += 1;
+                    bytes.push(match escaped {
+                        b'0' => 0,
+                        b'b' => 0x08,
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'Z' => 0x1a,
+                        other => other,
+                    });
+                }
+                // '' はシングルクォート自体のエスケープ
+                b'\'' if self.peek() == Some(b'\'') => {
+                    self.position += 1;
+                    bytes.push(b'\'');
+                }
+                b'\'' => return Ok(SqlValue::String(bytes)),
+                other => bytes.push(other),
+            }
+        }
+        self.position = start;
+        Err(self.error("string is not terminated"))
+    }
+
+    fn parse_hex(&mut self) -> Result<SqlValue, SqlDumpParseError> {
+        let start = self.position;
+        self.position += 2;
+        let digits_start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_hexdigit()) {
+            self.position += 1;
+        }
+        let digits = &self.line[digits_start..self.position];
+        if !digits.len().is_multiple_of(2) {
+            self.position = start;
+            return Err(self.error("hex literal has an odd number of digits"));
+        }
+        let bytes = digits
+            .chunks(2)
+            .map(|pair| {
+                u8::from_str_radix(std::str::from_utf8(pair).unwrap_or_default(), 16)
+                    .unwrap_or_default()
+            })
+            .collect();
+        Ok(SqlValue::String(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SqlDumpParseError, SqlValue, parse_dump};
+
+    const DUMP: &str = "\
+DROP TABLE IF EXISTS `gachadata`;
+CREATE TABLE `gachadata` (
+  `id` int(11) NOT NULL AUTO_INCREMENT,
+  `probability` double NOT NULL,
+  `itemstack` blob DEFAULT NULL,
+  `event_id` int(11) DEFAULT NULL,
+  PRIMARY KEY (`id`),
+  KEY `event_id` (`event_id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+INSERT INTO `gachadata` VALUES (1,0.01,'it\\'s (not), a row',NULL),(2,1e-3,'\\\\\\n',3);
+INSERT INTO `gachadata` VALUES (3,-0.5,0x00FF,NULL);
+-- Dump completed
+";
+
+    fn string(value: &str) -> SqlValue {
+        SqlValue::String(value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn columns_primary_key_and_rows_are_parsed() {
+        let tables = parse_dump(DUMP.as_bytes()).unwrap();
+        let gachadata = &tables["gachadata"];
+
+        assert_eq!(
+            gachadata.columns,
+            ["id", "probability", "itemstack", "event_id"]
+        );
+        assert_eq!(gachadata.primary_key, ["id"]);
+        assert_eq!(
+            gachadata.rows,
+            [
+                vec![
+                    SqlValue::Number("1".to_owned()),
+                    SqlValue::Number("0.01".to_owned()),
+                    string("it's (not), a row"),
+                    SqlValue::Null,
+                ],
+                vec![
+                    SqlValue::Number("2".to_owned()),
+                    SqlValue::Number("1e-3".to_owned()),
+                    string("\\\n"),
+                    SqlValue::Number("3".to_owned()),
+                ],
+                vec![
+                    SqlValue::Number("3".to_owned()),
+                    SqlValue::Number("-0.5".to_owned()),
+                    SqlValue::String(vec![0x00, 0xFF]),
+                    SqlValue::Null,
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_position() {
+        let truncated = DUMP.replace(",(2,1e-3,'\\\\\\n',3);", ",(2,1e-3");
+        assert_eq!(
+            parse_dump(truncated.as_bytes()),
+            Err(SqlDumpParseError {
+                line: 10,
+                column: 74,
+                reason: "row is truncated (missing `)`)".to_owned(),
+            })
+        );
+
+        let missing_value = DUMP.replace("(3,-0.5,0x00FF,NULL)", "(3,-0.5,NULL)");
+        let error = parse_dump(missing_value.as_bytes()).unwrap_err();
+        assert_eq!((error.line, error.column), (11, 32));
+        assert_eq!(
+            error.reason,
+            "row has 3 values but `gachadata` has 4 columns"
+        );
+
+        let broken_number = DUMP.replace("(3,-0.5,", "(3,-0.5.1-,");
+        let error = parse_dump(broken_number.as_bytes()).unwrap_err();
+        assert_eq!((error.line, error.column), (11, 35));
+        assert_eq!(error.reason, "invalid number `-0.5.1-`");
+    }
+
+    #[test]
+    fn values_are_rendered_as_json_and_sql() {
+        let values = [
+            SqlValue::Null,
+            SqlValue::Number("12".to_owned()),
+            SqlValue::Number("0.25".to_owned()),
+            string("it's"),
+            SqlValue::String(vec![0xAC, 0xED]),
+        ];
+        assert_eq!(
+            serde_json::to_string(&values).unwrap(),
+            r#"[null,12,0.25,"it's","0xACED"]"#
+        );
+        let rendered: Vec<_> = values.iter().map(ToString::to_string).collect();
+        assert_eq!(rendered, ["NULL", "12", "0.25", "'it''s'", "0xACED"]);
+    }
+}
@@ -1,6 +1,9 @@
 use opentelemetry::global;
-use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
-use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use opentelemetry_otlp::{MetricExporter, Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource, metrics::SdkMeterProvider, propagation::TraceContextPropagator,
+    trace::SdkTracerProvider,
+};
 
 const SERVICE_NAME: &str = "gachadata-server";
 
@@ -14,12 +17,7 @@ const SERVICE_NAME: &str = "gachadata-server";
 /// `OTEL_*` 環境変数から自動で読み込まれます。
 /// (seichi-portal-backend の telemetry.rs と同じ構成)
 pub fn init_tracer_provider() -> Option<SdkTracerProvider> {
-    let sdk_disabled =
-        std::env::var("OTEL_SDK_DISABLED").is_ok_and(|value| value.eq_ignore_ascii_case("true"));
-    let endpoint_configured =
-        std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_ok_and(|value| !value.is_empty());
-
-    if sdk_disabled || !endpoint_configured {
+    if !export_enabled() {
         return None;
     }
 
@@ -31,17 +29,9 @@ pub fn init_tracer_provider() -> Option<SdkTracerProvider> {
         .build()
         .expect("failed to build OTLP span exporter");
 
-    // Resource::builder() は OTEL_SERVICE_NAME / OTEL_RESOURCE_ATTRIBUTES を
-    // 自動で読むため、service.name は環境変数未設定時のみデフォルト値を与える
-    let resource = if std::env::var("OTEL_SERVICE_NAME").is_ok() {
-        Resource::builder().build()
-    } else {
-        Resource::builder().with_service_name(SERVICE_NAME).build()
-    };
-
     let provider = SdkTracerProvider::builder()
         .with_batch_exporter(exporter)
-        .with_resource(resource)
+        .with_resource(resource())
         .build();
 
     global::set_tracer_provider(provider.clone());
@@ -49,14 +39,58 @@ pub fn init_tracer_provider() -> Option<SdkTracerProvider> {
     Some(provider)
 }
 
+/// OpenTelemetry のメトリクスを初期化します。
+///
+/// 有効になる条件とエクスポート先は [`init_tracer_provider`] と同じです。
+/// 無効な場合、`global::meter` で作った計器への記録は何もしません。
+pub fn init_meter_provider() -> Option<SdkMeterProvider> {
+    if !export_enabled() {
+        return None;
+    }
+
+    let exporter = MetricExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpBinary)
+        .build()
+        .expect("failed to build OTLP metric exporter");
+
+    let provider = SdkMeterProvider::builder()
+        .with_periodic_exporter(exporter)
+        .with_resource(resource())
+        .build();
+
+    global::set_meter_provider(provider.clone());
+
+    Some(provider)
+}
+
+fn export_enabled() -> bool {
+    let sdk_disabled =
+        std::env::var("OTEL_SDK_DISABLED").is_ok_and(|value| value.eq_ignore_ascii_case("true"));
+    let endpoint_configured =
+        std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_ok_and(|value| !value.is_empty());
+
+    !sdk_disabled && endpoint_configured
+}
+
+fn resource() -> Resource {
+    // Resource::builder() は OTEL_SERVICE_NAME / OTEL_RESOURCE_ATTRIBUTES を
+    // 自動で読むため、service.name は環境変数未設定時のみデフォルト値を与える
+    if std::env::var("OTEL_SERVICE_NAME").is_ok() {
+        Resource::builder().build()
+    } else {
+        Resource::builder().with_service_name(SERVICE_NAME).build()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::init_tracer_provider;
+    use super::{init_meter_provider, init_tracer_provider};
 
     /// 環境変数の設定はプロセス全体に影響するため、
     /// 競合しないよう 1 つのテストで順に検証する。
     #[test]
-    fn tracer_provider_is_gated_by_environment_variables() {
+    fn providers_are_gated_by_environment_variables() {
         // SAFETY: このテストバイナリ内で環境変数を読み書きするのはこのテストだけ
         unsafe {
             std::env::remove_var("OTEL_EXPORTER_OTLP_ENDPOINT");
@@ -66,6 +100,7 @@ mod tests {
             init_tracer_provider().is_none(),
             "OTEL_EXPORTER_OTLP_ENDPOINT 未設定なら初期化をスキップする"
         );
+        assert!(init_meter_provider().is_none());
 
         unsafe {
             std::env::set_var("OTEL_EXPORTER_OTLP_ENDPOINT", "http://localhost:4318");
@@ -75,6 +110,7 @@ mod tests {
             init_tracer_provider().is_none(),
             "OTEL_SDK_DISABLED=true なら endpoint が設定されていてもスキップする"
         );
+        assert!(init_meter_provider().is_none());
 
         unsafe {
             std::env::remove_var("OTEL_SDK_DISABLED");
@@ -90,6 +126,14 @@ mod tests {
                 .shutdown()
                 .expect("tracer provider must shut down cleanly");
         }
+        let meter_provider = init_meter_provider();
+        assert!(
+            meter_provider.is_some(),
+            "endpoint 設定時は meter provider も初期化される"
+        );
+        if let Some(provider) = meter_provider {
+            let _ = provider.shutdown();
+        }
         unsafe {
             std::env::remove_var("OTEL_EXPORTER_OTLP_ENDPOINT");
         }
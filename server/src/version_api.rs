@@ -0,0 +1,346 @@
+use crate::changelog::to_markdown;
+use crate::config::Gacha;
+use crate::domain::{ContentHash, GachaDataRepository};
+use crate::dump_diff::{DumpDiff, diff_dumps};
+use crate::presentation::{internal_server_error, unix_secs};
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{ErrorResponse, IntoResponse, Response, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub struct VersionResponse {
+    /// `GET /versions/{id}/gachadata.sql` の `id` (dump のバージョン)
+    id: String,
+    published_at_unix_secs: u64,
+    size_bytes: usize,
+    row_counts: BTreeMap<String, u64>,
+    /// 現在公開中のバージョンかどうか
+    current: bool,
+}
+
+/// 履歴に残っている dump のバージョンを新しい順に返す
+#[tracing::instrument(skip(repository))]
+pub async fn get_versions_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+) -> Result<Json<Vec<VersionResponse>>> {
+    let current = repository
+        .gachadata_dump()
+        .map_err(internal_server_error)?
+        .content_hash;
+    let versions = repository
+        .gachadata_versions()
+        .map_err(internal_server_error)?;
+
+    Ok(Json(
+        versions
+            .into_iter()
+            .map(|version| VersionResponse {
+                current: current.as_ref() == Some(&version.content_hash),
+                id: version.content_hash.0,
+                published_at_unix_secs: unix_secs(version.published_at),
+                size_bytes: version.dump.0.len(),
+                row_counts: version.row_counts,
+            })
+            .collect(),
+    ))
+}
+
+/// 履歴に残っている dump をバージョンを指定してダウンロードする
+#[tracing::instrument(skip(repository))]
+pub async fn get_version_dump_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let version = repository
+        .gachadata_version(&ContentHash(id))
+        .map_err(internal_server_error)?
+        .ok_or_else(|| {
+            ErrorResponse::from(
+                (StatusCode::NOT_FOUND, "No dump with the given version.").into_response(),
+            )
+        })?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Disposition", "attachment; filename=gachadata.sql")
+        .header("Content-Type", "application/sql")
+        .header("X-Gachadata-Version", version.content_hash.to_string())
+        .header(header::ETAG, format!("\"{}\"", version.content_hash))
+        // 同じバージョンの中身は変わらない
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        .body(version.dump.0.into())
+        .unwrap())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffFormat {
+    #[default]
+    Json,
+    Text,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    #[serde(default)]
+    format: DiffFormat,
+}
+
+#[derive(Serialize)]
+pub struct DiffResponse {
+    from: String,
+    to: String,
+    #[serde(flatten)]
+    diff: DumpDiff,
+}
+
+/// 履歴に残っている 2 つのバージョンの差分を、主キーで突き合わせて返す
+///
+/// `?format=text` を付けると人が読むためのテキストで返す。
+#[tracing::instrument(skip(repository))]
+pub async fn get_version_diff_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    Path((from, to)): Path<(String, String)>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Response> {
+    let from = repository
+        .gachadata_version(&ContentHash(from))
+        .map_err(internal_server_error)?;
+    let to = repository
+        .gachadata_version(&ContentHash(to))
+        .map_err(internal_server_error)?;
+    let (Some(from), Some(to)) = (from, to) else {
+        return Err(ErrorResponse::from(
+            (StatusCode::NOT_FOUND, "No dump with the given version.").into_response(),
+        ));
+    };
+
+    let diff = diff_dumps(&from.dump.0, &to.dump.0)
+        .map_err(|err| internal_server_error(anyhow::anyhow!("failed to parse dump: {err}")))?;
+
+    Ok(match query.format {
+        DiffFormat::Json => Json(DiffResponse {
+            from: from.content_hash.0,
+            to: to.content_hash.0,
+            diff,
+        })
+        .into_response(),
+        DiffFormat::Text => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            format!("--- {}\n+++ {}\n{diff}", from.content_hash, to.content_hash),
+        )
+            .into_response(),
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangelogFormat {
+    #[default]
+    Json,
+    Markdown,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangelogQuery {
+    #[serde(default)]
+    format: ChangelogFormat,
+}
+
+#[derive(Serialize)]
+pub struct ChangelogResponse {
+    version: String,
+    /// 変更点の比較元のバージョン。直前のバージョンが履歴になければ `null`
+    previous_version: Option<String>,
+    published_at_unix_secs: u64,
+    changes: Vec<String>,
+}
+
+/// 履歴に残っているバージョンごとの変更点を新しい順に返す
+///
+/// `?format=markdown` を付けると Discord などに貼れる Markdown で返す。
+#[tracing::instrument(skip(repository, settings))]
+pub async fn get_changelog_handler(
+    State(repository): State<Arc<dyn GachaDataRepository>>,
+    State(settings): State<Arc<Gacha>>,
+    Query(query): Query<ChangelogQuery>,
+) -> Result<Response> {
+    let versions = repository
+        .gachadata_versions()
+        .map_err(internal_server_error)?;
+
+    Ok(match query.format {
+        ChangelogFormat::Json => Json(
+            versions
+                .into_iter()
+                .map(|version| {
+                    let (previous_version, changes) = match version.changelog {
+                        Some(changelog) => (Some(changelog.previous_version.0), changelog.changes),
+                        None => (None, Vec::new()),
+                    };
+                    ChangelogResponse {
+                        version: version.content_hash.0,
+                        previous_version,
+                        published_at_unix_secs: unix_secs(version.published_at),
+                        changes,
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )
+        .into_response(),
+        ChangelogFormat::Markdown => (
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            to_markdown(&versions, &settings.event_time_zone()),
+        )
+            .into_response(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DiffFormat, DiffQuery, get_version_diff_handler, get_version_dump_handler,
+        get_versions_handler,
+    };
+    use crate::domain::{
+        ContentHash, DumpVersion, GachaDataRepository, GachadataDump, GachadataDumpWithTime,
+    };
+    use crate::presentation::test_support::SlowRepository;
+    use axum::Json;
+    use axum::extract::{Path, Query, State};
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use bytes::Bytes;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[tokio::test]
+    async fn past_versions_are_listed_and_downloadable() {
+        let version = |dump: &'static [u8], published_at_secs: u64| DumpVersion {
+            content_hash: ContentHash::of(dump),
+            published_at: SystemTime::UNIX_EPOCH + Duration::from_secs(published_at_secs),
+            dump: GachadataDump(Bytes::from_static(dump)),
+            row_counts: BTreeMap::new(),
+            changelog: None,
+        };
+        let repository: Arc<dyn GachaDataRepository> = Arc::new(SlowRepository {
+            snapshot: Some(GachadataDumpWithTime {
+                dump: GachadataDump(Bytes::from_static(b"-- current")),
+                content_hash: Some(ContentHash::of(b"-- current")),
+                ..GachadataDumpWithTime::default()
+            }),
+            versions: vec![
+                version(b"-- current", 2_000),
+                version(b"-- previous", 1_000),
+            ],
+            ..SlowRepository::default()
+        });
+
+        let Json(versions) = get_versions_handler(State(Arc::clone(&repository)))
+            .await
+            .unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(versions[0].current);
+        assert!(!versions[1].current);
+        assert_eq!(versions[1].published_at_unix_secs, 1_000);
+
+        let response =
+            get_version_dump_handler(State(Arc::clone(&repository)), Path(versions[1].id.clone()))
+                .await
+                .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"-- previous");
+
+        let missing = get_version_dump_handler(State(repository), Path("unknown".to_owned())).await;
+        assert_eq!(missing.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn diff_between_versions_is_rendered_as_json_or_text() {
+        const BEFORE: &[u8] = b"CREATE TABLE `gachadata` (
+  `id` int(11) NOT NULL,
+  `probability` double NOT NULL,
+  PRIMARY KEY (`id`)
+);
+INSERT INTO `gachadata` VALUES (1,0.01);
+";
+        const AFTER: &[u8] = b"CREATE TABLE `gachadata` (
+  `id` int(11) NOT NULL,
+  `probability` double NOT NULL,
+  PRIMARY KEY (`id`)
+);
+INSERT INTO `gachadata` VALUES (1,0.02),(2,0.5);
+";
+        let version = |dump: &'static [u8]| DumpVersion {
+            content_hash: ContentHash::of(dump),
+            published_at: SystemTime::UNIX_EPOCH,
+            dump: GachadataDump(Bytes::from_static(dump)),
+            row_counts: BTreeMap::new(),
+            changelog: None,
+        };
+        let repository: Arc<dyn GachaDataRepository> = Arc::new(SlowRepository {
+            versions: vec![version(AFTER), version(BEFORE)],
+            ..SlowRepository::default()
+        });
+        let ids = || (ContentHash::of(BEFORE).0, ContentHash::of(AFTER).0);
+        let body = |response: Response| async {
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+        };
+
+        let response = get_version_diff_handler(
+            State(Arc::clone(&repository)),
+            Path(ids()),
+            Query(DiffQuery {
+                format: DiffFormat::Json,
+            }),
+        )
+        .await
+        .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body(response).await).unwrap();
+        assert_eq!(json["from"], ids().0);
+        assert_eq!(
+            json["tables"]["gachadata"]["added"],
+            serde_json::json!([{ "id": 2, "probability": 0.5 }])
+        );
+        assert_eq!(
+            json["tables"]["gachadata"]["changed"][0]["columns"]["probability"],
+            serde_json::json!({ "before": 0.01, "after": 0.02 })
+        );
+
+        let response = get_version_diff_handler(
+            State(Arc::clone(&repository)),
+            Path(ids()),
+            Query(DiffQuery {
+                format: DiffFormat::Text,
+            }),
+        )
+        .await
+        .unwrap();
+        let text = String::from_utf8(body(response).await.to_vec()).unwrap();
+        assert!(text.ends_with(
+            "gachadata: 1 added, 0 removed, 1 changed
+  + id=2 probability=0.5
+  ~ id=1 probability: 0.01 -> 0.02
+"
+        ));
+
+        let missing = get_version_diff_handler(
+            State(repository),
+            Path((ids().0, "unknown".to_owned())),
+            Query(DiffQuery {
+                format: DiffFormat::Json,
+            }),
+        )
+        .await;
+        assert_eq!(missing.into_response().status(), StatusCode::NOT_FOUND);
+    }
+}